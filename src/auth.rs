@@ -1,19 +1,107 @@
-use actix_web::{get, web, App, HttpServer, Responder};
+use actix_web::{web, App, HttpResponse, HttpServer};
 use base64::{engine::general_purpose, Engine as _};
+use rand::{distributions::Alphanumeric, Rng};
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{RequestBuilder, Url};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{io::ErrorKind, time::Duration};
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::mpsc::{self, Sender};
-use url_builder::URLBuilder;
+
+use crate::config::Config;
+use crate::error::{check, Result, SptError};
+
+/// Everything spt asks for at login, space-separated.
+pub const SCOPE: &str = "playlist-read-private playlist-read-collaborative playlist-modify-public playlist-modify-private user-read-currently-playing user-read-playback-state user-modify-playback-state user-library-read user-library-modify";
+
+/// The scopes spt asks for that `granted`, a token's space-separated scope, lacks; logins made
+/// by older versions of spt were not asked for all of them.
+pub fn missing_scopes(granted: &str) -> Vec<&'static str> {
+    let granted: Vec<&str> = granted.split_whitespace().collect();
+    SCOPE
+        .split_whitespace()
+        .filter(|scope| !granted.contains(scope))
+        .collect()
+}
 
 pub struct AppState {
-    pub tx: Sender<SpotifyAccessToken>,
-    pub id: String,
-    pub secret: String,
+    pub tx: Sender<Result<SpotifyAccessToken>>,
+    pub flow: AuthFlow,
+    pub state: String,
+    pub token_url: String,
+    pub redirect_uri: String,
+}
+
+/// How spt proves its identity to the accounts service.
+#[derive(Debug, Clone)]
+pub enum AuthFlow {
+    /// Authorization Code with PKCE: no secret, a one-time verifier instead.
+    Pkce {
+        client_id: String,
+        code_verifier: String,
+    },
+    /// Authorization Code with the app's client secret sent as Basic auth.
+    ClientSecret { client_id: String, secret: String },
+}
+
+impl AuthFlow {
+    /// Picks PKCE unless a client secret was configured.
+    pub fn new(client_id: String, secret: Option<String>) -> Self {
+        match secret {
+            Some(secret) => AuthFlow::ClientSecret { client_id, secret },
+            None => AuthFlow::Pkce {
+                client_id,
+                code_verifier: random_string(128),
+            },
+        }
+    }
+
+    pub fn client_id(&self) -> &str {
+        match self {
+            AuthFlow::Pkce { client_id, .. } | AuthFlow::ClientSecret { client_id, .. } => {
+                client_id
+            }
+        }
+    }
+
+    /// Authenticates a token request: Basic auth for the secret flow, `client_id` in the form
+    /// (plus the verifier, when exchanging a code) for PKCE.
+    fn authenticate(
+        &self,
+        req: RequestBuilder,
+        params: &mut Vec<(&'static str, String)>,
+        exchanging_code: bool,
+    ) -> RequestBuilder {
+        match self {
+            AuthFlow::ClientSecret { client_id, secret } => {
+                let to_encode = format!("{}:{}", client_id, secret);
+
+                let mut b64 = String::new();
+
+                general_purpose::STANDARD.encode_string(to_encode.as_bytes(), &mut b64);
+
+                req.header(AUTHORIZATION, format!("Basic {}", b64))
+            }
+            AuthFlow::Pkce {
+                client_id,
+                code_verifier,
+            } => {
+                params.push(("client_id", client_id.clone()));
+                if exchanging_code {
+                    params.push(("code_verifier", code_verifier.clone()));
+                }
+                req
+            }
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct SpotifyAuthInfo {
-    pub code: String,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -31,113 +119,250 @@ pub struct SpotifyRefreshToken {
     pub token_type: String,
     pub scope: String,
     pub expires_in: i64,
+    /// Present when Spotify rotates the refresh token, which it always does for PKCE clients.
+    pub refresh_token: Option<String>,
+}
+
+/// A random string drawn from the characters PKCE allows in a code verifier.
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// The S256 code challenge for `verifier`: unpadded base64url of its SHA-256 digest.
+pub fn code_challenge(verifier: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Trades an authorization code for an access and refresh token.
+pub async fn exchange_code(
+    token_url: &str,
+    flow: &AuthFlow,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<SpotifyAccessToken> {
+    let client = reqwest::Client::new();
+    let mut params = vec![
+        ("code", code.to_string()),
+        ("redirect_uri", redirect_uri.to_string()),
+        ("grant_type", "authorization_code".to_string()),
+    ];
+    let req = flow.authenticate(
+        client
+            .post(token_url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded"),
+        &mut params,
+        true,
+    );
+    let res = req.form(&params).send().await?;
+    Ok(check(res).await?.json::<SpotifyAccessToken>().await?)
+}
+
+fn login_page(title: &str, message: &str) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>spt</title></head>\
+         <body style=\"font-family: sans-serif; text-align: center; margin-top: 20vh\">\
+         <h1>{}</h1><p>{}</p></body></html>",
+        escape(title),
+        escape(message)
+    )
 }
 
-#[get("/callback/spotify")]
+/// Handles the redirect back from Spotify at the path of the configured redirect URI.
 pub async fn spotify_auth(
     app_code: web::Query<SpotifyAuthInfo>,
     app_data: web::Data<AppState>,
-) -> impl Responder {
-    let redirect_uri = "http://localhost:8888/callback/spotify";
+) -> HttpResponse {
+    if app_code.state.as_deref() != Some(app_data.state.as_str()) {
+        // Not our request: ignore it and keep waiting for the real redirect.
+        return HttpResponse::BadRequest()
+            .content_type("text/html; charset=utf-8")
+            .body(login_page(
+                "Login failed",
+                "The login request did not come from spt.",
+            ));
+    }
 
-    let to_encode = format!("{}:{}", app_data.id, app_data.secret);
+    let res = match (&app_code.code, &app_code.error) {
+        (Some(code), None) => {
+            exchange_code(
+                &app_data.token_url,
+                &app_data.flow,
+                code,
+                &app_data.redirect_uri,
+            )
+            .await
+        }
+        (_, error) => Err(SptError::Auth(
+            error
+                .clone()
+                .unwrap_or_else(|| "no code returned".to_string()),
+        )),
+    };
 
-    let mut b64 = String::new();
-
-    general_purpose::STANDARD.encode_string(to_encode.as_bytes(), &mut b64);
+    let body = match &res {
+        Ok(_) => login_page(
+            "Logged in to spt",
+            "You can close this tab and return to the terminal.",
+        ),
+        Err(e) => login_page("Login failed", &e.to_string()),
+    };
+    // The receiver is gone only if gsat already returned, so there is no one left to tell.
+    let _ = app_data.tx.send(res).await;
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(body)
+}
 
-    let client = reqwest::Client::new();
-    let params = [
-        ("code", app_code.code.as_str()),
-        ("redirect_uri", redirect_uri),
-        ("grant_type", "authorization_code"),
+/// Builds the authorize URL the user has to visit, tied to `state`.
+fn authorize_url(config: &Config, flow: &AuthFlow, redirect_uri: &str, state: &str) -> Result<Url> {
+    let mut params = vec![
+        ("response_type", "code".to_string()),
+        ("scope", SCOPE.to_string()),
+        ("client_id", flow.client_id().to_string()),
+        ("redirect_uri", redirect_uri.to_string()),
+        ("state", state.to_string()),
     ];
-    let res = client
-        .post("https://accounts.spotify.com/api/token")
-        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .header(AUTHORIZATION, format!("Basic {}", b64))
-        .form(&params)
-        .send()
-        .await
-        .expect("the access token request to send")
-        .json::<SpotifyAccessToken>()
-        .await
-        .expect("the access token response to decode");
+    if let AuthFlow::Pkce { code_verifier, .. } = flow {
+        params.push(("code_challenge_method", "S256".to_string()));
+        params.push(("code_challenge", code_challenge(code_verifier)));
+    }
+    Url::parse_with_params(&config.authorize_url(), &params)
+        .map_err(|e| SptError::Config(format!("accounts_url: {e}")))
+}
 
-    app_data.tx.send(res.clone()).await.unwrap();
-    format!("token: {}", res.access_token.clone())
+/// Extracts the authorization code from what the user pasted back: either the full URL the
+/// browser was redirected to, whose `state` must match, or just the `code` value.
+pub fn parse_redirect(input: &str, state: &str) -> Result<String> {
+    let input = input.trim();
+    let Ok(url) = Url::parse(input) else {
+        if input.is_empty() {
+            return Err(SptError::InvalidInput("no code was entered".to_string()));
+        }
+        return Ok(input.to_string());
+    };
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    if let Some(error) = param("error") {
+        return Err(SptError::Auth(error));
+    }
+    if param("state").as_deref() != Some(state) {
+        return Err(SptError::Auth("state mismatch".to_string()));
+    }
+    param("code").ok_or_else(|| SptError::InvalidInput("the URL has no code parameter".to_string()))
 }
 
-pub async fn gsat(
-    spotify_id: String,
-    spotify_secret: String,
-) -> Result<SpotifyAccessToken, anyhow::Error> {
-    let redirect_uri = "http://localhost:8888/callback/spotify";
+pub async fn gsat(config: &Config, flow: AuthFlow) -> Result<SpotifyAccessToken> {
+    let redirect = Url::parse(&config.redirect_uri)
+        .map_err(|e| SptError::Config(format!("redirect_uri: {e}")))?;
+    let (Some(host), Some(port)) = (redirect.host_str(), redirect.port_or_known_default()) else {
+        return Err(SptError::Config(format!(
+            "redirect_uri {} has no host to listen on",
+            config.redirect_uri
+        )));
+    };
 
-    let scope = "playlist-read-private playlist-read-collaborative playlist-modify-public playlist-modify-private user-read-currently-playing user-modify-playback-state";
+    let (tx, mut rx) = mpsc::channel::<Result<SpotifyAccessToken>>(8);
 
-    let (tx, mut rx) = mpsc::channel::<SpotifyAccessToken>(8);
+    let state = random_string(16);
+    let token_url = config.token_url();
+    let redirect_uri = config.redirect_uri.clone();
+    let callback_path = redirect.path().to_string();
+    let authorize_url = authorize_url(config, &flow, &redirect_uri, &state)?;
 
-    let id = spotify_id.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(AppState {
+                tx: tx.clone(),
+                flow: flow.clone(),
+                state: state.clone(),
+                token_url: token_url.clone(),
+                redirect_uri: redirect_uri.clone(),
+            }))
+            .route(&callback_path, web::get().to(spotify_auth))
+    })
+    .workers(1)
+    .bind((host, port))
+    .map_err(|e| match e.kind() {
+        ErrorKind::AddrInUse => SptError::Auth(format!(
+            "port {port} is already in use; free it or set redirect_uri to another registered port"
+        )),
+        _ => SptError::Auth(format!("could not listen on {host}:{port}: {e}")),
+    })?
+    .run();
+    let handle = server.handle();
+    tokio::spawn(server);
 
-    tokio::spawn(async {
-        let server = HttpServer::new(move || {
-            App::new()
-                .app_data(web::Data::new(AppState {
-                    tx: tx.clone(),
-                    id: id.clone(),
-                    secret: spotify_secret.clone(),
-                }))
-                .service(spotify_auth)
-        })
-        .bind(("127.0.0.1", 8888))
-        .unwrap()
-        .run();
-        server.await.unwrap();
-    });
+    if webbrowser::open(authorize_url.as_str()).is_err() {
+        // The server is already listening, so the redirect still arrives if the URL is opened
+        // by hand.
+        eprintln!("Could not open a browser. Open this URL to log in:\n\n{authorize_url}\n");
+    }
 
-    let mut ub = URLBuilder::new();
-    ub.set_protocol("https")
-        .set_host("accounts.spotify.com/authorize")
-        .add_param("response_type", "code")
-        .add_param("scope", scope)
-        .add_param("client_id", spotify_id.clone().as_str())
-        .add_param("redirect_uri", redirect_uri);
+    let timeout = Duration::from_secs(config.login_timeout_secs);
+    let res = match tokio::time::timeout(timeout, rx.recv()).await {
+        Ok(Some(res)) => res,
+        Ok(None) => Err(SptError::Auth("the login server stopped".to_string())),
+        Err(_) => Err(SptError::Auth(format!(
+            "no login within {}s, run spt again to retry",
+            timeout.as_secs()
+        ))),
+    };
+    handle.stop(true).await;
+    res
+}
 
-    webbrowser::open(ub.build().as_str())?;
+/// Logs in without a browser or local server: prints the authorize URL and reads the
+/// redirected URL (or bare code) back from stdin. The prompts go to stderr, like any other.
+pub async fn gsat_headless(config: &Config, flow: AuthFlow) -> Result<SpotifyAccessToken> {
+    let state = random_string(16);
+    let authorize_url = authorize_url(config, &flow, &config.redirect_uri, &state)?;
 
-    let new_token = rx.recv().await.unwrap();
-    Ok(new_token)
+    eprintln!("Open this URL in a browser and log in:\n\n{authorize_url}\n");
+    eprintln!("Then paste the URL you were redirected to (or its code parameter):");
+    let mut input = String::new();
+    BufReader::new(tokio::io::stdin())
+        .read_line(&mut input)
+        .await?;
+    let code = parse_redirect(&input, &state)?;
+
+    exchange_code(&config.token_url(), &flow, &code, &config.redirect_uri).await
 }
 
 pub async fn refresh_token(
+    config: &Config,
     refresh_token: String,
-    spotify_id: String,
-    spotify_secret: String,
-) -> Result<SpotifyRefreshToken, anyhow::Error> {
-    let url = "https://accounts.spotify.com/api/token";
-
-    let to_encode = format!("{}:{}", spotify_id, spotify_secret);
-
-    let mut b64 = String::new();
-
-    general_purpose::STANDARD.encode_string(to_encode.as_bytes(), &mut b64);
-
+    flow: &AuthFlow,
+) -> Result<SpotifyRefreshToken> {
     let client = reqwest::Client::new();
-    let params = [
+    let mut params = vec![
         ("refresh_token", refresh_token),
         ("grant_type", "refresh_token".to_string()),
     ];
-    let res = client
-        .post(url)
-        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-        .header(AUTHORIZATION, format!("Basic {}", b64))
-        .form(&params)
-        .send()
+    let req = flow.authenticate(
+        client
+            .post(config.token_url())
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded"),
+        &mut params,
+        false,
+    );
+    let res = req.form(&params).send().await?;
+    let res = check(res)
         .await
-        .unwrap()
+        .map_err(|e| SptError::Auth(format!("could not refresh the access token: {e}")))?
         .json::<SpotifyRefreshToken>()
-        .await
-        .unwrap();
+        .await?;
     Ok(res)
 }
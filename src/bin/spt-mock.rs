@@ -0,0 +1,21 @@
+use clap::{arg, command, value_parser};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let matches = command!()
+        .about("In-memory mock of the Spotify Web API for running spt offline")
+        .arg(arg!(--host <HOST> "Address to bind").default_value("127.0.0.1"))
+        .arg(
+            arg!(-p --port <PORT> "Port to bind")
+                .value_parser(value_parser!(u16))
+                .default_value("8899"),
+        )
+        .get_matches();
+    let host = matches.get_one::<String>("host").unwrap();
+    let port = *matches.get_one::<u16>("port").unwrap();
+
+    println!("Mock Spotify listening on http://{host}:{port}");
+    println!("  SPT_API_URL=http://{host}:{port}/v1");
+    println!("  SPT_ACCOUNTS_URL=http://{host}:{port}");
+    playlist::mock::run(host, port).await
+}
@@ -0,0 +1,381 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use playlist::error::Result;
+use playlist::spotify::{Offset, RepeatState, SeekTarget, VolumeChange};
+use playlist::uri::SpotifyUri;
+
+use crate::output::OutputFormat;
+
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+pub struct Cli {
+    /// Account profile to use
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Directory for spt's database, or :memory: to keep nothing
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<String>,
+
+    /// Log in by pasting the redirect URL instead of opening a browser
+    #[arg(long, global = true)]
+    pub no_browser: bool,
+
+    /// Device to play on, by name (fuzzy) or id, instead of the active one
+    #[arg(long, global = true, value_name = "DEVICE")]
+    pub device: Option<String>,
+
+    /// How to print results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Template for text output, e.g. "{artist} - {name} ({duration})"
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    pub format: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Control playback
+    #[command(subcommand)]
+    Player(PlayerCommand),
+    /// List, show and add to playlists
+    #[command(subcommand)]
+    Playlist(PlaylistCommand),
+    /// Show what is playing, where, and how
+    Status,
+    /// Jump within the current track
+    Seek {
+        /// A position like 1:23, or an offset like +30s or -10s
+        #[arg(allow_hyphen_values = true)]
+        position: SeekTarget,
+    },
+    /// Set the volume, or step it up or down
+    Volume {
+        /// A percentage from 0 to 100, or a step like +5 or -10
+        #[arg(allow_hyphen_values = true)]
+        change: VolumeChange,
+    },
+    /// Mute, remembering the volume for unmute
+    Mute,
+    /// Restore the volume from before mute
+    Unmute,
+    /// List the devices Spotify can play on, marking the active one
+    Devices,
+    /// Move playback to another device
+    Transfer {
+        /// Device name (fuzzy) or id
+        device: String,
+        /// Start playing on it, instead of keeping the current play state
+        #[arg(long)]
+        play: bool,
+    },
+    /// Play a whole playlist, album or artist, or a Spotify link
+    Play(PlayArgs),
+    /// Add the current track, or a linked one, to Liked Songs
+    Like { link: Option<SpotifyUri> },
+    /// Remove the current track, or a linked one, from Liked Songs
+    Unlike { link: Option<SpotifyUri> },
+    /// List Liked Songs, most recently added first, or check whether a track is in them
+    Liked(LikedArgs),
+    /// Show the current song and what plays after it, or manage spt's own queue
+    Queue(QueueArgs),
+    /// Search Spotify and pick songs to queue
+    Search(SearchArgs),
+    /// Log in or out of the active profile
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Manage account profiles
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+}
+
+impl Commands {
+    /// Whether the command acts on a device, so that `--device` applies to it.
+    pub fn targets_device(&self) -> bool {
+        match self {
+            Commands::Player(command) => !matches!(command, PlayerCommand::Current),
+            Commands::Seek { .. }
+            | Commands::Volume { .. }
+            | Commands::Mute
+            | Commands::Unmute
+            | Commands::Play(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The scopes Spotify checks for the requests the command makes, so that a 403 is only put
+    /// down to a scope the login lacks when the command needed it.
+    pub fn scopes(&self) -> &'static [&'static str] {
+        const CURRENTLY_PLAYING: &str = "user-read-currently-playing";
+        const READ_PLAYBACK: &str = "user-read-playback-state";
+        const MODIFY_PLAYBACK: &str = "user-modify-playback-state";
+        const READ_PRIVATE: &str = "playlist-read-private";
+        const READ_COLLABORATIVE: &str = "playlist-read-collaborative";
+        const MODIFY_PUBLIC: &str = "playlist-modify-public";
+        const MODIFY_PRIVATE: &str = "playlist-modify-private";
+        match self {
+            Commands::Player(PlayerCommand::Current) => &[CURRENTLY_PLAYING],
+            Commands::Player(_) | Commands::Search(_) => &[MODIFY_PLAYBACK],
+            Commands::Playlist(PlaylistCommand::List | PlaylistCommand::Show { .. }) => {
+                &[READ_PRIVATE, READ_COLLABORATIVE]
+            }
+            Commands::Playlist(PlaylistCommand::Add(_)) => &[
+                READ_PRIVATE,
+                READ_COLLABORATIVE,
+                MODIFY_PUBLIC,
+                MODIFY_PRIVATE,
+            ],
+            // Adds the current song to the playlist named "songs".
+            Commands::Playlist(PlaylistCommand::AddCurrent) => &[
+                CURRENTLY_PLAYING,
+                READ_PRIVATE,
+                READ_COLLABORATIVE,
+                MODIFY_PUBLIC,
+                MODIFY_PRIVATE,
+            ],
+            Commands::Status | Commands::Devices => &[READ_PLAYBACK],
+            Commands::Seek { .. } => &[CURRENTLY_PLAYING, MODIFY_PLAYBACK],
+            Commands::Volume { .. }
+            | Commands::Mute
+            | Commands::Unmute
+            | Commands::Transfer { .. } => &[READ_PLAYBACK, MODIFY_PLAYBACK],
+            Commands::Play(_) => &[READ_PRIVATE, READ_COLLABORATIVE, MODIFY_PLAYBACK],
+            Commands::Like { .. } | Commands::Unlike { .. } => {
+                &[CURRENTLY_PLAYING, "user-library-modify"]
+            }
+            Commands::Liked(_) => &[CURRENTLY_PLAYING, "user-library-read"],
+            Commands::Queue(_) => &[CURRENTLY_PLAYING, READ_PLAYBACK, MODIFY_PLAYBACK],
+            Commands::Auth(_) | Commands::Profile(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct PlayArgs {
+    #[command(subcommand)]
+    pub command: Option<PlayCommand>,
+    /// A spotify: URI or open.spotify.com link to a track, episode, album, artist, playlist or show
+    #[arg(required = true)]
+    pub link: Option<SpotifyUri>,
+    #[command(flatten)]
+    pub start: StartArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PlayCommand {
+    /// Play one of your playlists
+    Playlist {
+        name: String,
+        #[command(flatten)]
+        start: StartArgs,
+    },
+    /// Play the album that best matches a query
+    Album {
+        #[command(flatten)]
+        query: QueryArgs,
+        #[command(flatten)]
+        start: StartArgs,
+    },
+    /// Play the artist that best matches a query
+    Artist {
+        #[command(flatten)]
+        query: QueryArgs,
+        #[command(flatten)]
+        start: StartArgs,
+    },
+}
+
+/// Where in a playlist or album to start.
+#[derive(Debug, Args)]
+pub struct StartArgs {
+    /// Track to start from: its number, from 1, or its spotify:track: URI (not for artists)
+    #[arg(long)]
+    pub offset: Option<Offset>,
+    /// How far into that track to start, e.g. 1:23 or 90s
+    #[arg(long, value_name = "TIME")]
+    pub position: Option<SeekTarget>,
+}
+
+#[derive(Debug, Args)]
+pub struct LikedArgs {
+    #[command(subcommand)]
+    pub command: Option<LikedCommand>,
+    /// How many songs to list
+    #[arg(long, default_value_t = 50, conflicts_with = "all")]
+    pub limit: usize,
+    /// How many of the most recently added songs to skip
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+    /// List every liked song
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LikedCommand {
+    /// Show whether the current track, or a linked one, is in Liked Songs
+    Check { link: Option<SpotifyUri> },
+}
+
+#[derive(Debug, Args)]
+pub struct QueueArgs {
+    #[command(subcommand)]
+    pub command: Option<QueueCommand>,
+}
+
+/// spt's own queue, which unlike Spotify's can be reordered; `feed` hands it over song by song.
+#[derive(Debug, Subcommand)]
+pub enum QueueCommand {
+    /// List spt's queue
+    List,
+    /// Add the top search result or the picked ones to the end of spt's queue, or a link
+    /// straight to Spotify's
+    Add(QueueAddArgs),
+    /// Put the top search result, the picked ones, or a link first in spt's queue
+    InsertNext(QueueAddArgs),
+    /// Remove songs by position, e.g. 2, 1,3 or 2-4
+    Remove { positions: String },
+    /// Move the song at one position to another
+    Move { from: usize, to: usize },
+    /// Shuffle spt's queue
+    Shuffle,
+    /// Empty spt's queue
+    Clear,
+    /// Keep running, queueing the next song on Spotify shortly before the current one ends
+    Feed {
+        /// How many seconds before the end of a song to queue the next one
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        lead: u64,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct QueueAddArgs {
+    #[command(flatten)]
+    pub query: QueryArgs,
+    /// Add these results instead of the top one, e.g. 1,3 or 1-3
+    #[arg(long, value_name = "NUMBERS")]
+    pub pick: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    #[command(flatten)]
+    pub query: QueryArgs,
+    /// Queue these results without asking, e.g. 1,3 or 1-3
+    #[arg(long, value_name = "NUMBERS")]
+    pub queue: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    /// What to search for
+    #[arg(required = true, num_args = 1..)]
+    pub query: Vec<String>,
+}
+
+impl QueryArgs {
+    /// The words of the query joined back together, so it needs no quoting.
+    pub fn query(&self) -> String {
+        self.query.join(" ")
+    }
+
+    /// The playlist name and link of `playlist add NAME LINK`, if the last word is a link.
+    pub fn name_and_link(&self) -> Option<(String, Result<SpotifyUri>)> {
+        match self.query.as_slice() {
+            [name @ .., last] if !name.is_empty() && SpotifyUri::looks_like(last) => {
+                Some((name.join(" "), last.parse()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The link given instead of a query, if the query is a single URI or link.
+    pub fn link(&self) -> Option<Result<SpotifyUri>> {
+        match self.query.as_slice() {
+            [word] if SpotifyUri::looks_like(word) => Some(word.parse()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PlayerCommand {
+    /// Pause playback
+    Pause,
+    /// Resume playback
+    Resume,
+    /// Skip to the next song
+    Next,
+    /// Skip to the previous song
+    Prev,
+    /// Show the current song
+    Current,
+    /// Start playing songs matching a query
+    Start(QueryArgs),
+    /// Turn shuffle on or off
+    Shuffle {
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+    /// Set what to repeat
+    Repeat {
+        #[arg(value_enum)]
+        state: RepeatState,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PlaylistCommand {
+    /// List your playlists
+    List,
+    /// Show the songs of a playlist
+    Show {
+        /// Playlist name
+        name: String,
+    },
+    /// Search for songs and add some of them to a playlist, or add a link: NAME LINK
+    Add(QueryArgs),
+    /// Add the current song to the playlist named "songs"
+    AddCurrent,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthCommand {
+    /// Log in again, replacing the stored token
+    Login,
+    /// Forget the credentials and token of the active profile
+    Logout,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileCommand {
+    /// List profiles, marking the default
+    List,
+    /// Add a profile with its own client credentials
+    Add {
+        /// Profile name
+        name: String,
+    },
+    /// Remove a profile and its token
+    Remove {
+        /// Profile name
+        name: String,
+    },
+    /// Use a profile when --profile is not given
+    Default {
+        /// Profile name
+        name: String,
+    },
+}
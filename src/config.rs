@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use std::{env, fs, path::PathBuf};
+
+use crate::error::{Result, SptError};
+use crate::spotify::DEFAULT_API_URL;
+
+pub const DEFAULT_ACCOUNTS_URL: &str = "https://accounts.spotify.com";
+pub const DEFAULT_REDIRECT_URI: &str = "http://localhost:8888/callback/spotify";
+
+/// Settings read from `config.toml`, with `SPT_*` environment variables taking precedence.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub api_url: String,
+    pub accounts_url: String,
+    /// Must match a redirect URI registered for the Spotify app; spt listens on its host/port.
+    pub redirect_uri: String,
+    /// How long to wait for the browser login before giving up.
+    pub login_timeout_secs: u64,
+    pub storage: StorageConfig,
+    pub format: FormatConfig,
+}
+
+/// Default output templates, overridden by `--format`; see [`crate::template`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FormatConfig {
+    pub track: Option<String>,
+    pub playlist: Option<String>,
+    /// For `player current`; falls back to `track`.
+    pub current: Option<String>,
+}
+
+/// Where the client secret and tokens are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// Plain strings in the database, as before.
+    #[default]
+    Plain,
+    /// AES-256-GCM in the database, keyed by a passphrase or key file.
+    Encrypted,
+    /// The desktop keyring (Secret Service), falling back to `Encrypted` when there is none.
+    Keyring,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub secrets: SecretBackend,
+    /// Derive the encryption key from this file instead of asking for a passphrase; a leading
+    /// `~` stands for the home directory.
+    pub key_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_url: DEFAULT_API_URL.to_string(),
+            accounts_url: DEFAULT_ACCOUNTS_URL.to_string(),
+            redirect_uri: DEFAULT_REDIRECT_URI.to_string(),
+            login_timeout_secs: 300,
+            storage: StorageConfig::default(),
+            format: FormatConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let mut config = match Self::path() {
+            Some(path) if path.exists() => toml::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| SptError::Config(format!("{}: {e}", path.display())))?,
+            _ => Self::default(),
+        };
+        if let Ok(url) = env::var("SPT_API_URL") {
+            config.api_url = url;
+        }
+        if let Ok(url) = env::var("SPT_ACCOUNTS_URL") {
+            config.accounts_url = url;
+        }
+        if let Ok(uri) = env::var("SPT_REDIRECT_URI") {
+            config.redirect_uri = uri;
+        }
+        if let Ok(path) = env::var("SPT_KEY_FILE") {
+            config.storage.key_file = Some(PathBuf::from(path));
+        }
+        config.storage.key_file = config.storage.key_file.map(expand_home);
+        config.api_url = config.api_url.trim_end_matches('/').to_string();
+        config.accounts_url = config.accounts_url.trim_end_matches('/').to_string();
+        Ok(config)
+    }
+
+    /// `$SPT_CONFIG`, else `$XDG_CONFIG_HOME/spt/config.toml`, else `~/.config/spt/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(path) = env::var("SPT_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = match env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+        };
+        Some(config_home.join("spt").join("config.toml"))
+    }
+
+    pub fn token_url(&self) -> String {
+        format!("{}/api/token", self.accounts_url)
+    }
+
+    pub fn authorize_url(&self) -> String {
+        format!("{}/authorize", self.accounts_url)
+    }
+}
+
+/// Replaces a leading `~/` with `$HOME`, as a shell would; other paths are kept as written.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path,
+    }
+}
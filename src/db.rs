@@ -1,9 +1,16 @@
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, SystemTime};
-use surrealdb::engine::local::{Db, SpeeDb};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use surrealdb::engine::local::{Db, Mem, SpeeDb};
 use surrealdb::Surreal;
 
-use crate::SpotifyAccessToken;
+use playlist::auth::{AuthFlow, SpotifyAccessToken, SpotifyRefreshToken};
+use playlist::error::{Result, SptError};
+use playlist::spotify::Song;
+
+use crate::vault::Vault;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DBToken {
@@ -15,106 +22,567 @@ pub struct DBToken {
     pub time: SystemTime,
 }
 
+impl DBToken {
+    async fn sealed(mut self, db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<Self> {
+        self.access_token = vault
+            .seal(db, profile, "access_token", self.access_token)
+            .await?;
+        self.refresh_token = vault
+            .seal(db, profile, "refresh_token", self.refresh_token)
+            .await?;
+        Ok(self)
+    }
+
+    async fn opened(mut self, db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<Self> {
+        self.access_token = vault
+            .open(db, profile, "access_token", self.access_token)
+            .await?;
+        self.refresh_token = vault
+            .open(db, profile, "refresh_token", self.refresh_token)
+            .await?;
+        Ok(self)
+    }
+
+    /// Whether the token expires within `margin` from now.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        let elapsed = SystemTime::now()
+            .duration_since(self.time)
+            .unwrap_or_default();
+        elapsed + margin > Duration::new(self.expires_in as u64, 0)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientCredentials {
     pub client_id: String,
-    pub secret: String,
+    /// `None` selects the PKCE flow, which needs no secret.
+    pub secret: Option<String>,
+}
+
+impl ClientCredentials {
+    pub fn flow(&self) -> AuthFlow {
+        AuthFlow::new(self.client_id.clone(), self.secret.clone())
+    }
+
+    async fn sealed(mut self, db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<Self> {
+        if let Some(secret) = self.secret {
+            self.secret = Some(vault.seal(db, profile, "secret", secret).await?);
+        }
+        Ok(self)
+    }
+
+    async fn opened(mut self, db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<Self> {
+        if let Some(secret) = self.secret {
+            self.secret = Some(vault.open(db, profile, "secret", secret).await?);
+        }
+        Ok(self)
+    }
+}
+
+/// The profile used when neither `--profile` nor a stored default picks one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DefaultProfile {
+    profile: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MutedVolume {
+    volume: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LocalQueue {
+    songs: Vec<Song>,
 }
 
 pub async fn insert_client_credentials(
     db: &Surreal<Db>,
+    vault: &Vault,
+    profile: &str,
     creds: ClientCredentials,
-) -> surrealdb::Result<()> {
-    let existing_creds: Option<ClientCredentials> = db.select(("app", "creds")).await?;
+) -> Result<()> {
+    let creds = creds.sealed(db, vault, profile).await?;
+    let existing_creds: Option<ClientCredentials> = db.select(("creds", profile)).await?;
     if existing_creds.is_some() {
-        let _creds: Option<String> = db
-            .delete(("app", "creds"))
-            .await
-            .expect("should be able to delete creds");
+        let _creds: Option<ClientCredentials> = db.delete(("creds", profile)).await?;
     }
-    let _creds: Option<ClientCredentials> = db
-        .create(("app", "creds"))
-        .content(creds)
-        .await
-        .expect("should be able to create creds");
+    let _creds: Option<ClientCredentials> = db.create(("creds", profile)).content(creds).await?;
     Ok(())
 }
 
-pub async fn select_credentials(db: &Surreal<Db>) -> surrealdb::Result<Option<ClientCredentials>> {
-    let creds: Option<ClientCredentials> = db.select(("app", "creds")).await?;
-    Ok(creds)
+pub async fn select_credentials(
+    db: &Surreal<Db>,
+    vault: &Vault,
+    profile: &str,
+) -> Result<Option<ClientCredentials>> {
+    let creds: Option<ClientCredentials> = db.select(("creds", profile)).await?;
+    match creds {
+        Some(creds) => Ok(Some(creds.opened(db, vault, profile).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether `profile` has credentials stored, without decrypting them.
+pub async fn has_credentials(db: &Surreal<Db>, profile: &str) -> Result<bool> {
+    let creds: Option<ClientCredentials> = db.select(("creds", profile)).await?;
+    Ok(creds.is_some())
 }
 
-pub async fn delete_credentials(db: &Surreal<Db>) -> surrealdb::Result<()> {
-    let _creds: Vec<ClientCredentials> = db.delete("app").await?;
+pub async fn delete_credentials(db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<()> {
+    let creds: Option<ClientCredentials> = db.delete(("creds", profile)).await?;
+    if let Some(secret) = creds.and_then(|c| c.secret) {
+        vault.forget(profile, "secret", &secret);
+    }
     Ok(())
 }
 
+/// Every profile that has credentials stored, sorted by name.
+pub async fn list_profiles(db: &Surreal<Db>) -> Result<Vec<String>> {
+    let sql = "SELECT meta::id(id) AS name FROM type::table($table) ORDER BY name;";
+    let mut result = db.query(sql).bind(("table", "creds")).await?;
+    let profiles: Vec<String> = result.take((0, "name"))?;
+    Ok(profiles)
+}
+
+pub async fn select_default_profile(db: &Surreal<Db>) -> Result<Option<String>> {
+    let default: Option<DefaultProfile> = db.select(("settings", "default_profile")).await?;
+    Ok(default.map(|d| d.profile))
+}
+
+pub async fn set_default_profile(db: &Surreal<Db>, profile: &str) -> Result<()> {
+    let _default: Option<DefaultProfile> = db
+        .update(("settings", "default_profile"))
+        .content(DefaultProfile {
+            profile: profile.to_string(),
+        })
+        .await?;
+    Ok(())
+}
+
+pub async fn clear_default_profile(db: &Surreal<Db>) -> Result<()> {
+    let _default: Option<DefaultProfile> = db.delete(("settings", "default_profile")).await?;
+    Ok(())
+}
+
+/// The profile to use: `--profile` if given, else the stored default, else [`DEFAULT_PROFILE`].
+pub async fn active_profile(db: &Surreal<Db>, flag: Option<&str>) -> Result<String> {
+    match flag {
+        Some(profile) => Ok(profile.to_string()),
+        None => Ok(select_default_profile(db)
+            .await?
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())),
+    }
+}
+
 pub async fn insert_token(
     db: &Surreal<Db>,
+    vault: &Vault,
+    profile: &str,
     old_token: SpotifyAccessToken,
-) -> surrealdb::Result<()> {
+) -> Result<()> {
+    let token = DBToken {
+        access_token: old_token.access_token,
+        refresh_token: old_token.refresh_token,
+        time: SystemTime::now(),
+        token_type: old_token.token_type,
+        scope: old_token.scope,
+        expires_in: old_token.expires_in,
+    };
     let _token: Option<DBToken> = db
-        .create(("token", "noah"))
-        .content(DBToken {
-            access_token: old_token.access_token,
-            refresh_token: old_token.refresh_token,
-            time: SystemTime::now(),
-            token_type: old_token.token_type,
-            scope: old_token.scope,
-            expires_in: old_token.expires_in,
-        })
+        .create(("token", profile))
+        .content(token.sealed(db, vault, profile).await?)
         .await?;
     Ok(())
 }
 
-pub async fn delete_token(db: &Surreal<Db>) -> surrealdb::Result<()> {
-    let _token: Vec<DBToken> = db.delete("token").await?;
+pub async fn delete_token(db: &Surreal<Db>, vault: &Vault, profile: &str) -> Result<()> {
+    let token: Option<DBToken> = db.delete(("token", profile)).await?;
+    if let Some(token) = token {
+        vault.forget(profile, "access_token", &token.access_token);
+        vault.forget(profile, "refresh_token", &token.refresh_token);
+    }
     Ok(())
 }
 
-pub async fn select_token(db: &Surreal<Db>) -> surrealdb::Result<Option<SpotifyAccessToken>> {
-    let sql = "SELECT access_token, refresh_token, token_type, scope, expires_in FROM type::table($table);";
-    let mut result = db.query(sql).bind(("table", "token")).await?;
-    let token: Option<SpotifyAccessToken> = result.take(0)?;
-    Ok(token)
+/// Whether `profile` has a token stored, without decrypting it.
+pub async fn has_token(db: &Surreal<Db>, profile: &str) -> Result<bool> {
+    let token: Option<DBToken> = db.select(("token", profile)).await?;
+    Ok(token.is_some())
 }
 
-pub async fn check_refresh(db: &Surreal<Db>) -> surrealdb::Result<bool> {
-    match db.select(("token", "noah")).await {
-        Ok(token) => {
-            let token: DBToken = token.expect("Token must exist to refresh");
-            let curr = SystemTime::now();
-            let elapsed = curr.duration_since(token.time).unwrap();
-            Ok(elapsed > Duration::new(token.expires_in as u64, 0))
-        }
-        Err(e) => Err(e),
+pub async fn select_db_token(
+    db: &Surreal<Db>,
+    vault: &Vault,
+    profile: &str,
+) -> Result<Option<DBToken>> {
+    let token: Option<DBToken> = db.select(("token", profile)).await?;
+    match token {
+        Some(token) => Ok(Some(token.opened(db, vault, profile).await?)),
+        None => Ok(None),
     }
 }
 
-pub async fn update_token(db: &Surreal<Db>, new_access_token: String) -> surrealdb::Result<()> {
-    let old_token: DBToken = db
-        .select(("token", "noah"))
+/// Stores a refreshed access token, keeping the old refresh token unless Spotify rotated it.
+pub async fn update_token(
+    db: &Surreal<Db>,
+    vault: &Vault,
+    profile: &str,
+    refreshed: SpotifyRefreshToken,
+) -> Result<DBToken> {
+    let old_token = select_db_token(db, vault, profile)
         .await?
-        .expect("Must be an old token to update");
+        .ok_or_else(|| SptError::Storage(format!("no token to update for profile {profile}")))?;
+    let new_token = DBToken {
+        access_token: refreshed.access_token,
+        refresh_token: refreshed.refresh_token.unwrap_or(old_token.refresh_token),
+        time: SystemTime::now(),
+        token_type: refreshed.token_type,
+        scope: refreshed.scope,
+        expires_in: refreshed.expires_in,
+    };
     let _new_token: Option<DBToken> = db
-        .update(("token", "noah"))
-        .content(DBToken {
-            access_token: new_access_token,
-            refresh_token: old_token.refresh_token,
-            time: SystemTime::now(),
-            token_type: old_token.token_type,
-            scope: old_token.scope,
-            expires_in: old_token.expires_in,
-        })
+        .update(("token", profile))
+        .content(new_token.clone().sealed(db, vault, profile).await?)
+        .await?;
+    Ok(new_token)
+}
+
+/// Remembers the volume `profile` had before `spt mute`.
+pub async fn set_muted_volume(db: &Surreal<Db>, profile: &str, volume: u8) -> Result<()> {
+    let _muted: Option<MutedVolume> = db
+        .update(("muted", profile))
+        .content(MutedVolume { volume })
         .await?;
-    println!("updated token");
     Ok(())
 }
 
-pub async fn get_db() -> surrealdb::Result<Surreal<Db>> {
-    let db = Surreal::new::<SpeeDb>("/home/noah/.surrealdb/data/spotify.db").await?;
+/// The volume from before `spt mute`, forgetting it.
+pub async fn take_muted_volume(db: &Surreal<Db>, profile: &str) -> Result<Option<u8>> {
+    let muted: Option<MutedVolume> = db.delete(("muted", profile)).await?;
+    Ok(muted.map(|m| m.volume))
+}
+
+/// spt's own queue for `profile`, the song handed to Spotify next first.
+pub async fn select_local_queue(db: &Surreal<Db>, profile: &str) -> Result<Vec<Song>> {
+    let queue: Option<LocalQueue> = db.select(("local_queue", profile)).await?;
+    Ok(queue.map(|q| q.songs).unwrap_or_default())
+}
+
+pub async fn save_local_queue(db: &Surreal<Db>, profile: &str, songs: Vec<Song>) -> Result<()> {
+    let _queue: Option<LocalQueue> = db
+        .update(("local_queue", profile))
+        .content(LocalQueue { songs })
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_local_queue(db: &Surreal<Db>, profile: &str) -> Result<()> {
+    let _queue: Option<LocalQueue> = db.delete(("local_queue", profile)).await?;
+    Ok(())
+}
+
+/// Passing this as the data directory keeps everything in memory, for tests.
+pub const IN_MEMORY: &str = ":memory:";
+
+/// Where the SurrealDB database lives.
+#[derive(Debug, Clone)]
+pub enum DataDir {
+    Path(PathBuf),
+    Memory,
+}
+
+impl DataDir {
+    /// Resolves `--data-dir`, then `$SPT_DATA_DIR`, then `$XDG_DATA_HOME/spt`, falling back to
+    /// `~/.local/share/spt`.
+    pub fn resolve(flag: Option<&str>) -> Result<Self> {
+        let dir = match flag
+            .map(str::to_string)
+            .or_else(|| env::var("SPT_DATA_DIR").ok())
+        {
+            Some(dir) if dir == IN_MEMORY => return Ok(DataDir::Memory),
+            Some(dir) => PathBuf::from(dir),
+            None => match env::var("XDG_DATA_HOME") {
+                Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("spt"),
+                _ => env::var("HOME")
+                    .map(|home| PathBuf::from(home).join(".local/share/spt"))
+                    .map_err(|_| {
+                        SptError::Config("set --data-dir, SPT_DATA_DIR or HOME".to_string())
+                    })?,
+            },
+        };
+        Ok(DataDir::Path(dir))
+    }
+}
+
+/// How long to wait for another spt process, such as `spt queue feed`, to close the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database, waiting up to [`BUSY_TIMEOUT`] while another spt process has it open.
+pub async fn get_db(data_dir: &DataDir) -> Result<Surreal<Db>> {
+    let started = Instant::now();
+    loop {
+        match open_db(data_dir).await {
+            Err(e) if is_busy(&e) && started.elapsed() < BUSY_TIMEOUT => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            res => return res,
+        }
+    }
+}
+
+async fn open_db(data_dir: &DataDir) -> Result<Surreal<Db>> {
+    let db = match data_dir {
+        DataDir::Path(dir) => {
+            fs::create_dir_all(dir)?;
+            Surreal::new::<SpeeDb>(dir.join("spotify.db")).await?
+        }
+        DataDir::Memory => Surreal::new::<Mem>(()).await?,
+    };
     db.use_ns("my_ns").use_db("my_db").await?;
 
     Ok(db)
 }
+
+/// Whether `e` is SpeeDB refusing to open a database whose lock another process holds.
+pub fn is_busy(e: &SptError) -> bool {
+    matches!(e, SptError::Storage(message) if message.contains("lock file") || message.contains("/LOCK"))
+}
+
+/// How a command reaches the database: through a handle it keeps open, or by opening it for
+/// each use, so that other spt processes can get at it in between.
+#[derive(Clone)]
+pub enum Database {
+    Open(Surreal<Db>),
+    PerUse(DataDir),
+}
+
+impl Database {
+    pub async fn get(&self) -> Result<Surreal<Db>> {
+        match self {
+            Database::Open(db) => Ok(db.clone()),
+            Database::PerUse(data_dir) => get_db(data_dir).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use playlist::config::{SecretBackend, StorageConfig};
+
+    async fn memory_db() -> Surreal<Db> {
+        get_db(&DataDir::Memory).await.unwrap()
+    }
+
+    const PROFILE: &str = "test-profile";
+
+    fn plain() -> Vault {
+        Vault::new(&StorageConfig::default())
+    }
+
+    fn creds(client_id: &str) -> ClientCredentials {
+        ClientCredentials {
+            client_id: client_id.to_string(),
+            secret: Some(format!("{client_id}-secret")),
+        }
+    }
+
+    fn access_token() -> SpotifyAccessToken {
+        SpotifyAccessToken {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            scope: "user-read-playback-state".to_string(),
+            expires_in: 3600,
+            refresh_token: "refresh".to_string(),
+        }
+    }
+
+    fn song(id: &str) -> Song {
+        Song {
+            id: id.to_string(),
+            uri: format!("spotify:track:{id}"),
+            name: id.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 1000,
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_profiles() {
+        let (db, vault) = (memory_db().await, plain());
+        assert!(list_profiles(&db).await.unwrap().is_empty());
+        assert_eq!(active_profile(&db, None).await.unwrap(), DEFAULT_PROFILE);
+
+        insert_client_credentials(&db, &vault, "test-work", creds("work-id"))
+            .await
+            .unwrap();
+        insert_client_credentials(&db, &vault, "test-home", creds("home-id"))
+            .await
+            .unwrap();
+        // Storing again replaces the credentials.
+        insert_client_credentials(&db, &vault, "test-home", creds("other-id"))
+            .await
+            .unwrap();
+        assert_eq!(
+            list_profiles(&db).await.unwrap(),
+            ["test-home", "test-work"]
+        );
+        let home = select_credentials(&db, &vault, "test-home")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(home.client_id, "other-id");
+        assert_eq!(home.secret.as_deref(), Some("other-id-secret"));
+
+        set_default_profile(&db, "test-work").await.unwrap();
+        assert_eq!(active_profile(&db, None).await.unwrap(), "test-work");
+        assert_eq!(
+            active_profile(&db, Some("test-home")).await.unwrap(),
+            "test-home"
+        );
+        clear_default_profile(&db).await.unwrap();
+        assert_eq!(active_profile(&db, None).await.unwrap(), DEFAULT_PROFILE);
+
+        delete_credentials(&db, &vault, "test-work").await.unwrap();
+        assert!(!has_credentials(&db, "test-work").await.unwrap());
+        assert_eq!(list_profiles(&db).await.unwrap(), ["test-home"]);
+    }
+
+    #[tokio::test]
+    async fn round_trips_tokens() {
+        let (db, vault) = (memory_db().await, plain());
+        assert!(!has_token(&db, PROFILE).await.unwrap());
+        insert_token(&db, &vault, PROFILE, access_token())
+            .await
+            .unwrap();
+        let token = select_db_token(&db, &vault, PROFILE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token, "refresh");
+        assert_eq!(token.scope, "user-read-playback-state");
+        assert!(!token.expires_within(Duration::from_secs(60)));
+
+        // Spotify only sometimes sends a new refresh token; the old one is kept otherwise.
+        let refreshed = |refresh_token: Option<&str>| SpotifyRefreshToken {
+            access_token: "access2".to_string(),
+            token_type: "Bearer".to_string(),
+            scope: "user-read-playback-state".to_string(),
+            expires_in: 3600,
+            refresh_token: refresh_token.map(str::to_string),
+        };
+        let token = update_token(&db, &vault, PROFILE, refreshed(None))
+            .await
+            .unwrap();
+        assert_eq!(token.access_token, "access2");
+        assert_eq!(token.refresh_token, "refresh");
+        update_token(&db, &vault, PROFILE, refreshed(Some("refresh2")))
+            .await
+            .unwrap();
+        let token = select_db_token(&db, &vault, PROFILE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.refresh_token, "refresh2");
+
+        delete_token(&db, &vault, PROFILE).await.unwrap();
+        assert!(select_db_token(&db, &vault, PROFILE)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn encrypts_secrets_at_rest() {
+        let key_file = env::temp_dir().join(format!("spt-test-key-{}", std::process::id()));
+        fs::write(&key_file, "not a real key").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&key_file, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        let vault = Vault::new(&StorageConfig {
+            secrets: SecretBackend::Encrypted,
+            key_file: Some(key_file.clone()),
+        });
+        let db = memory_db().await;
+
+        insert_token(&db, &vault, PROFILE, access_token())
+            .await
+            .unwrap();
+        insert_client_credentials(&db, &vault, PROFILE, creds("id"))
+            .await
+            .unwrap();
+        let stored: DBToken = db.select(("token", PROFILE)).await.unwrap().unwrap();
+        assert_ne!(stored.access_token, "access");
+        assert_ne!(stored.refresh_token, "refresh");
+        let token = select_db_token(&db, &vault, PROFILE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.access_token, "access");
+        let creds = select_credentials(&db, &vault, PROFILE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(creds.secret.as_deref(), Some("id-secret"));
+        fs::remove_file(key_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_the_local_queue_per_profile() {
+        let db = memory_db().await;
+        assert!(select_local_queue(&db, PROFILE).await.unwrap().is_empty());
+
+        save_local_queue(&db, PROFILE, vec![song("a"), song("b")])
+            .await
+            .unwrap();
+        save_local_queue(&db, "test-work", vec![song("c")])
+            .await
+            .unwrap();
+        let uris = |songs: Vec<Song>| songs.into_iter().map(|s| s.uri).collect::<Vec<_>>();
+        assert_eq!(
+            uris(select_local_queue(&db, PROFILE).await.unwrap()),
+            ["spotify:track:a", "spotify:track:b"]
+        );
+
+        save_local_queue(&db, PROFILE, vec![song("b")])
+            .await
+            .unwrap();
+        assert_eq!(
+            uris(select_local_queue(&db, PROFILE).await.unwrap()),
+            ["spotify:track:b"]
+        );
+        delete_local_queue(&db, PROFILE).await.unwrap();
+        assert!(select_local_queue(&db, PROFILE).await.unwrap().is_empty());
+        assert_eq!(
+            uris(select_local_queue(&db, "test-work").await.unwrap()),
+            ["spotify:track:c"]
+        );
+    }
+
+    #[tokio::test]
+    async fn takes_the_muted_volume_once() {
+        let db = memory_db().await;
+        assert_eq!(take_muted_volume(&db, PROFILE).await.unwrap(), None);
+        set_muted_volume(&db, PROFILE, 40).await.unwrap();
+        assert_eq!(take_muted_volume(&db, PROFILE).await.unwrap(), Some(40));
+        assert_eq!(take_muted_volume(&db, PROFILE).await.unwrap(), None);
+    }
+
+    #[test]
+    fn resolves_the_in_memory_data_dir() {
+        assert!(matches!(
+            DataDir::resolve(Some(IN_MEMORY)).unwrap(),
+            DataDir::Memory
+        ));
+        assert!(matches!(
+            DataDir::resolve(Some("/tmp/spt")).unwrap(),
+            DataDir::Path(dir) if dir == std::path::Path::new("/tmp/spt")
+        ));
+    }
+}
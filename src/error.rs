@@ -0,0 +1,123 @@
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::retry::retry_after;
+
+pub type Result<T> = std::result::Result<T, SptError>;
+
+#[derive(Debug, Error)]
+pub enum SptError {
+    #[error("no active device, start playback in a Spotify app first")]
+    NoActiveDevice,
+    #[error("this action requires Spotify Premium")]
+    PremiumRequired,
+    #[error("rate limited by Spotify{}", retry_after.map(|d| format!(", retry in {}s", d.as_secs())).unwrap_or_default())]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("the access token was rejected, log in again")]
+    AuthExpired,
+    #[error("Spotify refused this ({message}), and the login lacks permissions spt now asks for ({scopes}), run `spt auth login` to grant them")]
+    MissingScopes { scopes: String, message: String },
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("nothing is currently playing")]
+    NothingPlaying,
+    #[error("Spotify returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("could not reach Spotify: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("storage error: {0}")]
+    Storage(String),
+    #[error("authorization failed: {0}")]
+    Auth(String),
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl SptError {
+    /// The process exit code the CLI reports for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SptError::InvalidInput(_) => 2,
+            SptError::NoActiveDevice => 3,
+            SptError::PremiumRequired => 4,
+            SptError::RateLimited { .. } => 5,
+            SptError::AuthExpired | SptError::MissingScopes { .. } => 6,
+            SptError::NotFound(_) => 7,
+            SptError::NothingPlaying => 8,
+            SptError::Api { .. } => 9,
+            SptError::Network(_) => 10,
+            SptError::Storage(_) => 11,
+            SptError::Auth(_) => 12,
+            SptError::Config(_) => 13,
+            SptError::Io(_) => 14,
+        }
+    }
+
+    /// Decodes Spotify's `{"error": {status, message, reason}}` body from a failed response.
+    pub async fn from_response(res: Response) -> Self {
+        let status = res.status();
+        let retry_after = retry_after(res.headers());
+        let body = res.text().await.unwrap_or_default();
+        let (message, reason) = match serde_json::from_str::<ErrorBody>(&body) {
+            Ok(ErrorBody {
+                error: ErrorDetail::Regular { message, reason },
+                ..
+            }) => (message, reason),
+            Ok(ErrorBody {
+                error: ErrorDetail::Auth(error),
+                error_description,
+            }) => (error_description.unwrap_or(error), None),
+            Err(_) => (body, None),
+        };
+
+        match (status, reason.as_deref()) {
+            (_, Some("NO_ACTIVE_DEVICE")) => SptError::NoActiveDevice,
+            (_, Some("PREMIUM_REQUIRED")) => SptError::PremiumRequired,
+            (StatusCode::UNAUTHORIZED, _) => SptError::AuthExpired,
+            (StatusCode::TOO_MANY_REQUESTS, _) => SptError::RateLimited { retry_after },
+            (StatusCode::NOT_FOUND, _) => SptError::NotFound(message),
+            _ => SptError::Api {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<surrealdb::Error> for SptError {
+    fn from(e: surrealdb::Error) -> Self {
+        SptError::Storage(e.to_string())
+    }
+}
+
+/// Returns `res` unchanged if it succeeded, otherwise the decoded error.
+pub async fn check(res: Response) -> Result<Response> {
+    if res.status().is_success() {
+        Ok(res)
+    } else {
+        Err(SptError::from_response(res).await)
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+    error_description: Option<String>,
+}
+
+/// The Web API nests an object under `error`; the accounts service uses a bare string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorDetail {
+    Regular {
+        message: String,
+        reason: Option<String>,
+    },
+    Auth(String),
+}
@@ -0,0 +1,113 @@
+//! `spt queue feed`: hands spt's own queue to Spotify one song at a time.
+//!
+//! Spotify's queue can only be appended to, so songs wait in spt's queue, where they can still
+//! be moved or removed, until shortly before the current song ends.
+
+use std::time::Duration;
+
+use playlist::error::{Result, SptError};
+use playlist::spotify::SpotifyClient;
+
+use crate::db::{self, Database};
+use crate::output::Output;
+
+/// How often the player is checked while there is nothing to hand over yet.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Polls the player and queues the head of spt's queue on Spotify near the end of each song.
+///
+/// The database only allows one process at a time and the other `spt queue` commands need it
+/// while the feeder runs, so it is opened just to read or change the queue and closed before
+/// anything goes to Spotify.
+pub struct Feeder {
+    db: Database,
+    client: SpotifyClient,
+    profile: String,
+    lead: Duration,
+    /// The track that was playing when a song was last handed over, so each play hands over one.
+    fed_during: Option<String>,
+}
+
+impl Feeder {
+    pub fn new(db: Database, client: SpotifyClient, profile: String, lead: Duration) -> Self {
+        Self {
+            db,
+            client,
+            profile,
+            lead,
+            fed_during: None,
+        }
+    }
+
+    /// Runs until interrupted with Ctrl-C, reporting passing failures and carrying on.
+    pub async fn run(mut self, output: &Output) -> Result<()> {
+        output.note("Feeding spt's queue to Spotify, press Ctrl-C to stop.");
+        loop {
+            let wait = match self.tick(output).await {
+                Ok(wait) => wait,
+                Err(e) if is_transient(&e) => {
+                    output.error(&e);
+                    POLL_INTERVAL
+                }
+                Err(e) => return Err(e),
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => (),
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+        }
+    }
+
+    /// Hands over the next song if the current one is about to end, and returns how long to
+    /// wait before checking again.
+    async fn tick(&mut self, output: &Output) -> Result<Duration> {
+        let current = match self.client.get_currently_playing().await {
+            Ok(current) => current,
+            Err(SptError::NothingPlaying) => return Ok(POLL_INTERVAL),
+            Err(e) => return Err(e),
+        };
+        let remaining = (current.song.duration_ms - current.progress_ms).max(0) as u64;
+        let remaining = Duration::from_millis(remaining);
+        if remaining > self.lead {
+            self.fed_during = None;
+            return Ok(POLL_INTERVAL.min(remaining - self.lead));
+        }
+        if !current.is_playing || self.fed_during.as_deref() == Some(current.song.uri.as_str()) {
+            return Ok(POLL_INTERVAL);
+        }
+
+        let next = {
+            let db = self.db.get().await?;
+            db::select_local_queue(&db, &self.profile).await?
+        }
+        .into_iter()
+        .next();
+        let Some(next) = next else {
+            return Ok(POLL_INTERVAL);
+        };
+        self.client.add_to_queue(&next.uri).await?;
+        {
+            // The queue may have been edited meanwhile, so the song is taken out wherever it is.
+            let db = self.db.get().await?;
+            let mut songs = db::select_local_queue(&db, &self.profile).await?;
+            if let Some(i) = songs.iter().position(|song| song.uri == next.uri) {
+                songs.remove(i);
+                db::save_local_queue(&db, &self.profile, songs).await?;
+            }
+        }
+        self.fed_during = Some(current.song.uri);
+        output.note("Queued:");
+        output.item(&next)?;
+        Ok(POLL_INTERVAL)
+    }
+}
+
+/// Errors worth waiting out: Spotify or the network failing for a moment, or another spt
+/// process keeping the database open.
+fn is_transient(e: &SptError) -> bool {
+    match e {
+        SptError::Network(_) | SptError::RateLimited { .. } | SptError::NoActiveDevice => true,
+        SptError::Api { status, .. } => *status >= 500,
+        _ => db::is_busy(e),
+    }
+}
@@ -1 +1,9 @@
+pub mod auth;
+pub mod config;
+pub mod error;
+pub mod mock;
 pub mod models;
+pub mod retry;
+pub mod spotify;
+pub mod template;
+pub mod uri;
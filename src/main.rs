@@ -1,332 +1,760 @@
-use clap::{arg, command};
-use std::{
-    fmt::Display,
-    io::{stdin, stdout, Write},
-};
+use clap::Parser;
+use futures::{StreamExt, TryStreamExt};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::{stderr, stdin, Write};
+use std::time::Duration;
+use surrealdb::{engine::local::Db, Surreal};
 
-mod auth;
+mod cli;
 mod db;
-mod spotify;
+mod feeder;
+mod output;
+mod token;
+mod vault;
 
-use auth::*;
-use db::{check_refresh, update_token, ClientCredentials};
-use spotify::*;
+use cli::{
+    AuthCommand, Cli, Commands, LikedArgs, LikedCommand, PlayArgs, PlayCommand, PlayerCommand,
+    PlaylistCommand, ProfileCommand, QueueAddArgs, QueueArgs, QueueCommand, SearchArgs, Toggle,
+};
+use db::{ClientCredentials, DataDir, Database};
+use feeder::Feeder;
+use output::{Output, Render, Templates};
+use playlist::auth::*;
+use playlist::config::Config;
+use playlist::error::{Result, SptError};
+use playlist::spotify::{
+    find_device, Device, PlaybackContext, Playlist, QueuedSong, SeekTarget, Song, SpotifyClient,
+    Volume, VolumeChange,
+};
+use playlist::template;
+use playlist::uri::{SpotifyUri, UriKind};
+use token::TokenManager;
+use vault::Vault;
 
-#[derive(Debug)]
-pub struct Playlist {
-    name: String,
-    owner: String,
-    id: String,
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let output = Output::new(cli.output);
+    if let Err(e) = run(cli, &output).await {
+        output.error(&e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Asks on stderr, so stdout only ever carries results.
+fn prompt(message: &str) -> Result<String> {
+    eprintln!("{message}");
+    let mut input = String::new();
+    stderr().flush()?;
+    stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Parses a 1-based selection from a numbered list of `len` entries.
+fn parse_index(input: &str, len: usize) -> Result<usize> {
+    let invalid = || {
+        SptError::InvalidInput(format!(
+            "expected a number between 1 and {len}, got \"{}\"",
+            input.trim()
+        ))
+    };
+    let i = input.trim().parse::<usize>().map_err(|_| invalid())?;
+    position(i, len).map_err(|_| invalid())
+}
+
+/// The index of 1-based `position` in a list of `len`.
+fn position(position: usize, len: usize) -> Result<usize> {
+    if (1..=len).contains(&position) {
+        Ok(position - 1)
+    } else {
+        Err(SptError::InvalidInput(format!(
+            "expected a position between 1 and {len}, got {position}"
+        )))
+    }
+}
+
+/// Comma-separated numbers and ranges, e.g. `1,3` or `2-4`, as indices in the order given.
+fn parse_indices(input: &str, len: usize) -> Result<Vec<usize>> {
+    let mut indices = vec![];
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse_index(from, len)?, parse_index(to, len)?);
+                if from > to {
+                    return Err(SptError::InvalidInput(format!(
+                        "range \"{}\" runs backwards",
+                        part.trim()
+                    )));
+                }
+                indices.extend(from..=to);
+            }
+            None => indices.push(parse_index(part, len)?),
+        }
+    }
+    Ok(indices)
 }
 
-#[derive(Debug)]
-pub struct Song {
+fn prompt_credentials() -> Result<ClientCredentials> {
+    Ok(ClientCredentials {
+        client_id: prompt("Enter spotify client id:")?,
+        secret: Some(prompt(
+            "Enter spotify client secret (leave empty to log in with PKCE):",
+        )?)
+        .filter(|secret| !secret.is_empty()),
+    })
+}
+
+/// A row of `spt profile list`.
+#[derive(Serialize)]
+struct ProfileEntry {
     name: String,
-    album: String,
-    artist: String,
-    uri: String,
+    default: bool,
 }
 
-impl Display for Song {
+impl Display for ProfileEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} | {} | {}", self.name, self.artist, self.album)
+        let marker = if self.default { "*" } else { " " };
+        write!(f, "{marker} {}", self.name)
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let db = db::get_db().await.expect("The db should exist");
-    let creds = db::select_credentials(&db)
-        .await
-        .expect("Credentials to exist");
-    if creds.is_none() {
-        let mut input = String::new();
-        println!("Enter spotify client id:");
-        stdout().flush().unwrap();
-        stdin().read_line(&mut input).unwrap();
-        let client_id = input.trim();
-        let mut input = String::new();
-        println!("Enter spotify client secret:");
-        stdout().flush().unwrap();
-        stdin().read_line(&mut input).unwrap();
-        let secret = input.trim();
-        db::insert_client_credentials(
-            &db,
-            ClientCredentials {
-                client_id: client_id.to_string(),
-                secret: secret.to_string(),
-            },
-        )
-        .await
-        .expect("Should be able to insert client creds");
-    }
-    let creds = db::select_credentials(&db)
-        .await
-        .expect("Credentials to exist")
-        .expect("And to be real");
-    let db_token = db::select_token(&db).await.expect("A db token to exist");
-    if db_token.is_none() {
-        let new_token = gsat(creds.client_id.clone(), creds.secret.clone())
-            .await
-            .unwrap();
-        db::insert_token(&db, new_token).await.unwrap();
-        println!("Fetched a new access token.");
+impl Render for ProfileEntry {}
+
+/// Whether a track is in Liked Songs; serializes as the track's fields plus `liked`.
+#[derive(Debug, Serialize)]
+struct LikedStatus {
+    #[serde(flatten)]
+    song: Song,
+    liked: bool,
+}
+
+impl Display for LikedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let not = if self.liked { "" } else { "not " };
+        write!(f, "{}: {not}in Liked Songs", self.song)
     }
-    if check_refresh(&db)
-        .await
-        .expect("Should be able to check refresh")
-    {
-        println!("Refreshing token...");
-        if let Some(token) = db_token {
-            let refreshed_token = refresh_token(
-                token.refresh_token,
-                creds.client_id.clone(),
-                creds.secret.clone(),
-            )
-            .await
-            .unwrap();
-            update_token(&db, refreshed_token.access_token.clone())
-                .await
-                .expect("Should be able to update the db with the new token");
+}
+
+impl Render for LikedStatus {}
+
+async fn profile_command(
+    db: &Surreal<Db>,
+    vault: &Vault,
+    output: &Output,
+    command: ProfileCommand,
+) -> Result<()> {
+    match command {
+        ProfileCommand::List => {
+            let default = db::active_profile(db, None).await?;
+            let profiles: Vec<ProfileEntry> = db::list_profiles(db)
+                .await?
+                .into_iter()
+                .map(|name| ProfileEntry {
+                    default: name == default,
+                    name,
+                })
+                .collect();
+            output.list(&profiles)?;
+        }
+        ProfileCommand::Add { name } => {
+            db::insert_client_credentials(db, vault, &name, prompt_credentials()?).await?;
+            output.done(
+                "profile_add",
+                &format!("Added profile {name}, log in with `spt --profile {name} auth login`."),
+            )?;
+        }
+        ProfileCommand::Remove { name } => {
+            if !db::has_credentials(db, &name).await? {
+                return Err(SptError::NotFound(format!("profile \"{name}\"")));
+            }
+            db::delete_credentials(db, vault, &name).await?;
+            db::delete_token(db, vault, &name).await?;
+            db::delete_local_queue(db, &name).await?;
+            db::take_muted_volume(db, &name).await?;
+            if db::select_default_profile(db).await?.as_deref() == Some(name.as_str()) {
+                db::clear_default_profile(db).await?;
+            }
+            output.done("profile_remove", &format!("Removed profile {name}."))?;
+        }
+        ProfileCommand::Default { name } => {
+            if !db::has_credentials(db, &name).await? {
+                return Err(SptError::NotFound(format!("profile \"{name}\"")));
+            }
+            db::set_default_profile(db, &name).await?;
+            output.done(
+                "profile_default",
+                &format!("Using profile {name} by default."),
+            )?;
         }
     }
+    Ok(())
+}
 
-    let token = db::select_token(&db)
-        .await
-        .expect("A db token to exist")
-        .expect("The new db token to exist by now");
+fn find_playlist<'a>(playlists: &'a [Playlist], name: &str) -> Result<&'a Playlist> {
+    playlists
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| SptError::NotFound(format!("playlist \"{name}\"")))
+}
 
-    let matches = command!()
-        .arg(arg!(-P --playlist <NAME> "Search a playlist").required(false))
-        .arg(arg!(-a --playlists ... "View all playlists").required(false))
-        .arg(arg!(-x --pause ... "Pause playback").required(false))
-        .arg(arg!(-r --resume ... "Resume playback").required(false))
-        .arg(arg!(-e --start <QUERY> "Start playing QUERY related songs").required(false))
-        .arg(arg!(-n --next ... "Skip to next song").required(false))
-        .arg(arg!(-p --prev ... "Skip to previous song").required(false))
-        .arg(arg!(-c --current ... "View current song").required(false))
-        .arg(arg!(-C --add_current ... "Add current song to playlist").required(false))
-        .arg(arg!(-S --shuffle <STATE> "Shuffle Y/N").required(false))
-        .arg(arg!(-R --repeat <STATE> "Repeat (track, context, off)").required(false))
-        .arg(arg!(-s --search <QUERY> "Search spotify").required(false))
-        .arg(arg!(-u --update <QUERY> "Add tracks to a playlist").required(false))
-        .arg(arg!(-v --volume <NUMBER> "Set playback volume").required(false))
-        .arg(arg!(-q --logout ... "Logout").required(false))
-        .get_matches();
-    if let Some(name) = matches.get_one::<String>("playlist") {
-        let playlists = get_all_playlists(token.clone())
-            .await
-            .expect("There should be playlists to return");
+async fn player_command(
+    client: &SpotifyClient,
+    output: &Output,
+    command: PlayerCommand,
+) -> Result<()> {
+    match command {
+        PlayerCommand::Pause => {
+            client.pause().await?;
+            output.done("pause", "")
+        }
+        PlayerCommand::Resume => {
+            client.resume().await?;
+            output.done("resume", "")
+        }
+        PlayerCommand::Next => {
+            client.skip_to_next().await?;
+            output.done("next", "")
+        }
+        PlayerCommand::Prev => {
+            client.skip_to_prev().await?;
+            output.done("prev", "")
+        }
+        PlayerCommand::Current => output.item(&client.get_currently_playing().await?),
+        PlayerCommand::Start(args) => {
+            let search_res = client.search_for_item(&args.query()).await?;
+            if output.is_text() {
+                println!("Playing the following songs:");
+            }
+            output.list(&search_res)?;
+            let uris = search_res.into_iter().map(|song| song.uri).collect();
+            client.start_playing(uris, None).await
+        }
+        PlayerCommand::Shuffle { state } => {
+            client.shuffle(state == Toggle::On).await?;
+            output.done("shuffle", "")
+        }
+        PlayerCommand::Repeat { state } => {
+            client.repeat(state).await?;
+            output.done("repeat", "")
+        }
+    }
+}
 
-        let name = name.trim();
-        let curr_playlist = playlists
-            .iter()
-            .find(|p| p.name == name)
-            .expect("User must enter a valid playlist name");
+async fn playlist_command(
+    client: &SpotifyClient,
+    output: &Output,
+    command: PlaylistCommand,
+) -> Result<()> {
+    match command {
+        PlaylistCommand::List => output.list(&client.get_all_playlists().await?),
+        PlaylistCommand::Show { name } => {
+            let playlists = client.get_all_playlists().await?;
+            let curr_playlist = find_playlist(&playlists, name.trim())?;
 
-        let songs = get_playlist(token.clone(), curr_playlist.id.clone())
-            .await
-            .expect("There should be playlists to return");
-        for song in songs {
-            println!("{song}")
+            output.list(&client.get_playlist(&curr_playlist.id).await?)
         }
-    };
-    if let Some(query) = matches.get_one::<String>("search") {
-        let query = query.trim();
-        let search_res = search_for_item(token.clone(), query)
-            .await
-            .expect("There should be search results to return");
+        PlaylistCommand::Add(args) => {
+            if let Some((name, link)) = args.name_and_link() {
+                let playlists = client.get_all_playlists().await?;
+                let playlist = find_playlist(&playlists, &name)?;
+                let song = item_from_link(client, link?, "added to a playlist").await?;
+                client
+                    .add_to_playlist(&playlist.id, vec![song.uri.clone()])
+                    .await?;
+                output.note(&format!("Added to {}:", playlist.name));
+                return output.list(&[song]);
+            }
+            let search_res = client.search_for_item(&args.query()).await?;
 
-        for (i, song) in search_res.iter().enumerate() {
-            println!("{}. {song}", i + 1);
-        }
-        println!("\nEnter a number to add a song to queue, or q to exit");
-        let mut input = String::new();
-        stdout().flush().unwrap();
-        stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-        match input {
-            "q" => (),
-            _ => {
-                let index = input.parse::<usize>().unwrap();
-                let uri = search_res[index - 1].uri.clone();
-                add_to_queue(token.clone(), uri.clone())
-                    .await
-                    .expect("Should be able to add to queue");
+            output.note("Songs:");
+            for (i, song) in search_res.iter().enumerate() {
+                output.note(&format!("{}. {}", i + 1, song.render(output.templates())));
+            }
+            output.note("Your playlists:");
+            let playlists = client.get_all_playlists().await?;
+            for playlist in playlists.iter() {
+                output.note(&playlist.render(output.templates()));
+            }
+            let playlist_name =
+                prompt("\nEnter the name of a playlist to add songs to, or q to exit")?;
+            if playlist_name == "q" {
+                return Ok(());
             }
+            let curr_playlist = find_playlist(&playlists, &playlist_name)?;
+            let ids = prompt(&format!(
+                "\nEnter comma-separated numbers to add songs to {}, or q to exit",
+                playlist_name
+            ))?;
+            if ids == "q" {
+                return Ok(());
+            }
+            let songs: Vec<Song> = parse_indices(&ids, search_res.len())?
+                .into_iter()
+                .map(|id| search_res[id].clone())
+                .collect();
+            let uris = songs.iter().map(|song| song.uri.clone()).collect();
+            client.add_to_playlist(&curr_playlist.id, uris).await?;
+            if output.is_text() {
+                return Ok(());
+            }
+            output.list(&songs)
         }
-    };
-    if let Some(query) = matches.get_one::<String>("update") {
-        let query = query.trim();
-        let search_res = search_for_item(token.clone(), query)
-            .await
-            .expect("There should be search results to return");
+        PlaylistCommand::AddCurrent => {
+            client.add_current_to_playlist().await?;
+            output.done("add_current", "")
+        }
+    }
+}
 
-        println!("Songs:");
-        for (i, song) in search_res.iter().enumerate() {
-            println!("{}. {song}", i + 1);
-        }
-        println!("Your playlists:");
-        let playlists = get_all_playlists(token.clone())
-            .await
-            .expect("There should be playlists to return");
-        for playlist in playlists.iter() {
-            println!("{} | {}", playlist.name, playlist.owner)
-        }
-        println!("\nEnter the name of a playlist to add songs to, or q to exit");
-        let mut input = String::new();
-        stdout().flush().unwrap();
-        stdin().read_line(&mut input).unwrap();
-        let playlist_name = input.trim();
-        //not sure if this return works
-        match playlist_name {
-            "q" => (),
-            _ => {
-                let curr_playlist = playlists.iter().find(|p| p.name == playlist_name).unwrap();
-                let pid = curr_playlist.id.clone();
-                let prompt = format!(
-                    "\nEnter comma-separated numbers to add songs to {}, or q to exit",
-                    playlist_name
-                );
-                println!("{}", prompt);
-                let mut input = String::new();
-                stdout().flush().unwrap();
-                stdin().read_line(&mut input).unwrap();
-                let ids = input.trim();
-                match ids {
-                    "q" => (),
-                    _ => {
-                        let uris: Vec<String> = ids
-                            .split(",")
-                            .map(|id| id.parse::<usize>().unwrap() - 1)
-                            .map(|id| search_res[id].uri.clone())
-                            .collect();
-                        add_to_playlist(token.clone(), pid, uris)
-                            .await
-                            .expect("To be able to add songs to playlist");
-                    }
-                }
-            }
+/// Starts a link, or a playlist, album or artist as the context so Spotify carries on through it.
+async fn play_command(client: &SpotifyClient, output: &Output, args: PlayArgs) -> Result<()> {
+    let (context, start) = match args.command {
+        Some(PlayCommand::Playlist { name, start }) => {
+            let playlists = client.get_all_playlists().await?;
+            let playlist = find_playlist(&playlists, name.trim())?;
+            let context = PlaybackContext {
+                kind: "playlist".to_string(),
+                uri: playlist.uri.clone(),
+                name: Some(playlist.name.clone()),
+            };
+            (context, start)
+        }
+        Some(PlayCommand::Album { query, start }) => {
+            (client.find_album(&query.query()).await?, start)
+        }
+        Some(PlayCommand::Artist { query, start }) => {
+            (client.find_artist(&query.query()).await?, start)
+        }
+        None => {
+            // Clap requires a link whenever there is no subcommand.
+            let Some(link) = args.link else {
+                return Ok(());
+            };
+            let context = PlaybackContext {
+                kind: link.kind.as_str().to_string(),
+                uri: link.uri(),
+                name: None,
+            };
+            (context, args.start)
         }
     };
-    if let Some(query) = matches.get_one::<String>("start") {
-        let query = query.trim();
-        let search_res = search_for_item(token.clone(), query)
-            .await
-            .expect("There should be playlists to return");
-        println!("Playing the following songs:");
-        for song in search_res.iter() {
-            println!("{song}");
-        }
-        let mut uris = vec![];
-        for song in search_res {
-            uris.push(song.uri);
-        }
-        start_playing(token.clone(), uris)
-            .await
-            .expect("Should be able to play the song");
+    let position_ms = match start.position {
+        None => None,
+        Some(SeekTarget::Absolute(ms)) => Some(ms),
+        Some(SeekTarget::Relative(_)) => {
+            return Err(SptError::InvalidInput(
+                "--position is a time from the start of the track, e.g. 1:23".to_string(),
+            ))
+        }
     };
-    if let Some(state) = matches.get_one::<String>("shuffle") {
-        let state = state.trim().to_lowercase();
-        let shuffle_state = {
-            if state == "y" {
-                true
-            } else {
-                false
-            }
+    if start.offset.is_some() {
+        let not_for = match context.kind.as_str() {
+            "track" | "episode" => Some("a single track"),
+            // Spotify refuses offsets for artist contexts with a bare 400.
+            "artist" => Some("an artist"),
+            _ => None,
         };
-        shuffle(token.clone(), shuffle_state)
-            .await
-            .expect("Should be able to shuffle");
-    };
-    // make things like this into an enum
-    if let Some(state) = matches.get_one::<String>("repeat") {
-        let state = state.trim().to_lowercase();
-        repeat(token.clone(), state)
-            .await
-            .expect("Should be able to repeat");
-    };
-    if let Some(num) = matches.get_one::<String>("volume") {
-        const MAX_VOLUME: usize = 100;
-        let mut num = num
-            .trim()
-            .parse::<usize>()
-            .expect("The volume to be a number");
-        if num > MAX_VOLUME {
-            num = MAX_VOLUME;
-        }
-        volume(token.clone(), num)
-            .await
-            .expect("Should be able to set volume");
-    };
-    match matches.get_one::<u8>("playlists") {
-        Some(0) => (),
-        _ => {
-            let playlists = get_all_playlists(token.clone())
-                .await
-                .expect("There should be playlists to return");
-            for playlist in playlists {
-                println!("{} | {}", playlist.name, playlist.owner)
+        if let Some(not_for) = not_for {
+            return Err(SptError::InvalidInput(format!(
+                "--offset picks a track in a playlist or album, not in {not_for}"
+            )));
+        }
+    }
+    if matches!(context.kind.as_str(), "track" | "episode") {
+        client
+            .start_playing(vec![context.uri.clone()], position_ms)
+            .await?;
+    } else {
+        client
+            .start_context(&context.uri, start.offset.as_ref(), position_ms)
+            .await?;
+    }
+    let name = context.name.as_deref().unwrap_or(&context.uri);
+    output.done("play", &format!("Playing {} {name}.", context.kind))
+}
+
+/// The track or episode behind `link`, for commands that take a single song.
+async fn item_from_link(client: &SpotifyClient, link: SpotifyUri, action: &str) -> Result<Song> {
+    if !link.is_item() {
+        return Err(SptError::InvalidInput(format!(
+            "only tracks and episodes can be {action}, play {link} with `spt play` instead"
+        )));
+    }
+    client.get_item(&link).await
+}
+
+/// The track `link` points at, or the current one, for the Liked Songs commands.
+async fn liked_target(client: &SpotifyClient, link: Option<SpotifyUri>) -> Result<Song> {
+    let song = match link {
+        Some(link) if link.kind != UriKind::Track => {
+            return Err(SptError::InvalidInput(format!(
+                "only tracks can be in Liked Songs, not {link}"
+            )))
+        }
+        Some(link) => client.get_item(&link).await?,
+        None => {
+            let current = client.get_currently_playing().await?;
+            if current.kind != "track" {
+                return Err(SptError::InvalidInput(format!(
+                    "only tracks can be in Liked Songs, and an {} is playing",
+                    current.kind
+                )));
             }
+            current.song
         }
     };
-    match matches.get_one::<u8>("pause") {
-        Some(0) => (),
-        _ => {
-            pause(token.clone()).await.expect("Should be able to pause");
+    if song.id.is_empty() {
+        return Err(SptError::InvalidInput(format!(
+            "{} is a local file, which cannot be in Liked Songs",
+            song.name
+        )));
+    }
+    Ok(song)
+}
+
+/// The search results `--pick` chose, or the top one.
+async fn pick_songs(client: &SpotifyClient, args: &QueueAddArgs) -> Result<Vec<Song>> {
+    if let Some(link) = args.query.link() {
+        if args.pick.is_some() {
+            return Err(SptError::InvalidInput(
+                "--pick picks search results, not a link".to_string(),
+            ));
         }
-    };
-    match matches.get_one::<u8>("resume") {
-        Some(0) => (),
-        _ => {
-            resume(token.clone())
-                .await
-                .expect("Should be able to resume");
+        return Ok(vec![item_from_link(client, link?, "queued").await?]);
+    }
+    let query = args.query.query();
+    let search_res = client.search_for_item(&query).await?;
+    match &args.pick {
+        Some(picked) => Ok(parse_indices(picked, search_res.len())?
+            .into_iter()
+            .map(|i| search_res[i].clone())
+            .collect()),
+        None => match search_res.into_iter().next() {
+            Some(song) => Ok(vec![song]),
+            None => Err(SptError::NotFound(format!("songs matching \"{query}\""))),
+        },
+    }
+}
+
+/// Edits spt's own queue; `spt queue feed` hands it to Spotify.
+async fn queue_command(
+    db: &Surreal<Db>,
+    profile: &str,
+    client: &SpotifyClient,
+    output: &Output,
+    command: QueueCommand,
+) -> Result<()> {
+    let mut songs = db::select_local_queue(db, profile).await?;
+    match command {
+        QueueCommand::List => {
+            if songs.is_empty() {
+                output.note("spt's queue is empty.");
+            }
+            let queued: Vec<QueuedSong> = songs
+                .into_iter()
+                .enumerate()
+                .map(|(i, song)| QueuedSong {
+                    position: i + 1,
+                    song,
+                })
+                .collect();
+            output.list(&queued)
         }
-    };
-    match matches.get_one::<u8>("next") {
-        Some(0) => (),
-        _ => {
-            skip_to_next(token.clone())
-                .await
-                .expect("There should be a next song");
+        QueueCommand::Add(args) => {
+            let added = pick_songs(client, &args).await?;
+            if args.query.link().is_some() {
+                // A link is queued on Spotify directly, without waiting for `spt queue feed`.
+                for song in &added {
+                    client.add_to_queue(&song.uri).await?;
+                }
+                output.note("Added to Spotify's queue:");
+                return output.list(&added);
+            }
+            songs.extend(added.iter().cloned());
+            db::save_local_queue(db, profile, songs).await?;
+            output.note("Added to spt's queue:");
+            output.list(&added)
         }
-    };
-    match matches.get_one::<u8>("prev") {
-        Some(0) => (),
-        _ => {
-            skip_to_prev(token.clone())
-                .await
-                .expect("There should be a previous song");
+        QueueCommand::InsertNext(args) => {
+            let added = pick_songs(client, &args).await?;
+            songs.splice(0..0, added.iter().cloned());
+            db::save_local_queue(db, profile, songs).await?;
+            output.note("Added to the front of spt's queue:");
+            output.list(&added)
         }
-    };
-    match matches.get_one::<u8>("current") {
-        Some(0) => (),
-        _ => {
-            let song = get_currently_playing(token.clone())
-                .await
-                .expect("There should be a current song");
-            println!("{song}")
+        QueueCommand::Remove { positions } => {
+            let mut picked = parse_indices(&positions, songs.len())?;
+            picked.sort_unstable();
+            picked.dedup();
+            // From the back, so the indices still to remove stay valid.
+            let mut removed: Vec<Song> = picked.iter().rev().map(|&i| songs.remove(i)).collect();
+            removed.reverse();
+            db::save_local_queue(db, profile, songs).await?;
+            output.note("Removed from spt's queue:");
+            output.list(&removed)
+        }
+        QueueCommand::Move { from, to } => {
+            let (from, to) = (position(from, songs.len())?, position(to, songs.len())?);
+            let song = songs.remove(from);
+            let message = format!("Moved {} to position {}.", song.name, to + 1);
+            songs.insert(to, song);
+            db::save_local_queue(db, profile, songs).await?;
+            output.done("queue_move", &message)
+        }
+        QueueCommand::Shuffle => {
+            songs.shuffle(&mut rand::thread_rng());
+            db::save_local_queue(db, profile, songs).await?;
+            output.done("queue_shuffle", "Shuffled spt's queue.")
+        }
+        QueueCommand::Clear => {
+            db::save_local_queue(db, profile, vec![]).await?;
+            output.done("queue_clear", "Cleared spt's queue.")
         }
+        QueueCommand::Feed { .. } => Ok(()),
+    }
+}
+
+/// The id and name of the device `query` names, for commands that target a device.
+async fn resolve_device(client: &SpotifyClient, query: &str) -> Result<(String, String)> {
+    let devices = client.devices().await?;
+    let Device { id, name, .. } = find_device(&devices, query)?;
+    match id {
+        Some(id) => Ok((id.clone(), name.clone())),
+        None => Err(SptError::InvalidInput(format!(
+            "{name} cannot be controlled through the Spotify API"
+        ))),
+    }
+}
+
+/// The volume of the device commands go to: the `--device` one, else the active one.
+async fn current_volume(client: &SpotifyClient) -> Result<u8> {
+    let device = match client.device() {
+        Some(id) => client
+            .devices()
+            .await?
+            .into_iter()
+            .find(|d| d.id.as_deref() == Some(id))
+            .ok_or_else(|| SptError::NotFound(format!("device {id}")))?,
+        None => client.playback_state().await?.device,
     };
-    match matches.get_one::<u8>("logout") {
-        Some(0) => (),
-        _ => {
-            db::delete_credentials(&db)
-                .await
-                .expect("Was able to delete credentials");
-            db::delete_token(&db)
-                .await
-                .expect("User was able to logout");
-            println!("Logged out successfully.")
+    device
+        .volume
+        .and_then(|v| u8::try_from(v).ok())
+        .ok_or_else(|| SptError::InvalidInput(format!("{} has no volume control", device.name)))
+}
+
+/// Queues the results picked with `--queue`; otherwise lists them and, in text mode, offers
+/// to queue some.
+async fn search(client: &SpotifyClient, output: &Output, args: SearchArgs) -> Result<()> {
+    let search_res = client.search_for_item(&args.query.query()).await?;
+
+    let picked = match args.queue {
+        Some(picked) => picked,
+        None => {
+            output.numbered(&search_res)?;
+            if !output.is_text() {
+                return Ok(());
+            }
+            let input =
+                prompt("\nEnter numbers to add songs to queue, e.g. 1,3 or 1-3, or q to exit")?;
+            if input == "q" {
+                return Ok(());
+            }
+            input
         }
     };
-    match matches.get_one::<u8>("add_current") {
-        Some(0) => (),
-        _ => {
-            add_current_to_playlist(token.clone())
-                .await
-                .expect("Should be able to add current song to playlist");
+    let songs: Vec<Song> = parse_indices(&picked, search_res.len())?
+        .into_iter()
+        .map(|id| search_res[id].clone())
+        .collect();
+    // One at a time, so they land in the queue in the order given.
+    for song in &songs {
+        client.add_to_queue(&song.uri).await?;
+    }
+    output.note("Queued:");
+    output.list(&songs)
+}
+
+async fn run(cli: Cli, output: &Output) -> Result<()> {
+    let config = Config::load()?;
+    let templates = Templates::load(&config.format, cli.format.as_deref())?;
+    let output = &output.clone().with_templates(templates);
+    let data_dir = DataDir::resolve(cli.data_dir.as_deref())?;
+    let db = db::get_db(&data_dir).await?;
+    let vault = Vault::new(&config.storage);
+    let profile = match cli.command {
+        Commands::Profile(command) => return profile_command(&db, &vault, output, command).await,
+        _ => db::active_profile(&db, cli.profile.as_deref()).await?,
+    };
+    if let Commands::Auth(AuthCommand::Logout) = cli.command {
+        db::delete_credentials(&db, &vault, &profile).await?;
+        db::delete_token(&db, &vault, &profile).await?;
+        return output.done("logout", "Logged out successfully.");
+    }
+
+    let creds = db::select_credentials(&db, &vault, &profile).await?;
+    let creds = match creds {
+        Some(creds) => creds,
+        None => {
+            let creds = prompt_credentials()?;
+            db::insert_client_credentials(&db, &vault, &profile, creds.clone()).await?;
+            creds
         }
     };
+    let login = matches!(cli.command, Commands::Auth(AuthCommand::Login));
+    if login || !db::has_token(&db, &profile).await? {
+        let new_token = if cli.no_browser {
+            gsat_headless(&config, creds.flow()).await?
+        } else {
+            gsat(&config, creds.flow()).await?
+        };
+        // The old token stays usable until a login has actually produced a new one.
+        db::delete_token(&db, &vault, &profile).await?;
+        db::insert_token(&db, &vault, &profile, new_token).await?;
+        output.note("Fetched a new access token.");
+    }
+    if let Commands::Queue(QueueArgs {
+        command: Some(QueueCommand::Feed { lead }),
+    }) = cli.command
+    {
+        if matches!(data_dir, DataDir::Memory) {
+            return Err(SptError::InvalidInput(
+                "spt queue feed needs a data directory to read the queue from".to_string(),
+            ));
+        }
+        // The feeder opens the database only while it uses it, leaving it free in between.
+        drop(db);
+        let db = Database::PerUse(data_dir);
+        let tokens =
+            TokenManager::new(db.clone(), vault, profile.clone(), config.clone(), creds).await?;
+        let client = SpotifyClient::new(tokens).with_base_url(&config.api_url);
+        let lead = Duration::from_secs(lead);
+        return Feeder::new(db, client, profile, lead).run(output).await;
+    }
+    let tokens = TokenManager::new(
+        Database::Open(db.clone()),
+        vault.clone(),
+        profile.clone(),
+        config.clone(),
+        creds,
+    )
+    .await?;
+    let mut needed = cli.command.scopes().to_vec();
+    if cli.device.is_some() && cli.command.targets_device() {
+        needed.push("user-read-playback-state");
+    }
+    let missing: Vec<&str> = missing_scopes(&tokens.scope().await)
+        .into_iter()
+        .filter(|scope| needed.contains(scope))
+        .collect();
+    let client = SpotifyClient::new(tokens).with_base_url(&config.api_url);
+    let result = async {
+        let mut client = client;
+        // Other commands ignore --device, so they don't need the device list.
+        if let Some(query) = cli.device.as_ref().filter(|_| cli.command.targets_device()) {
+            let (id, _) = resolve_device(&client, query).await?;
+            client = client.with_device(id);
+        }
+        match cli.command {
+            Commands::Player(command) => player_command(&client, output, command).await,
+            Commands::Playlist(command) => playlist_command(&client, output, command).await,
+            Commands::Search(args) => search(&client, output, args).await,
+            Commands::Play(command) => play_command(&client, output, command).await,
+            Commands::Like { link } => {
+                let song = liked_target(&client, link).await?;
+                client.like(&song.id).await?;
+                output.done("like", &format!("Added {} to Liked Songs.", song.name))
+            }
+            Commands::Unlike { link } => {
+                let song = liked_target(&client, link).await?;
+                client.unlike(&song.id).await?;
+                output.done(
+                    "unlike",
+                    &format!("Removed {} from Liked Songs.", song.name),
+                )
+            }
+            Commands::Liked(LikedArgs {
+                command: Some(LikedCommand::Check { link }),
+                ..
+            }) => {
+                let song = liked_target(&client, link).await?;
+                let liked = client.is_liked(&song.id).await?;
+                output.item(&LikedStatus { song, liked })
+            }
+            Commands::Liked(args) => {
+                let liked = client.liked(args.offset);
+                let songs: Vec<Song> = if args.all {
+                    liked.try_collect().await?
+                } else {
+                    liked.take(args.limit).try_collect().await?
+                };
+                output.list(&songs)
+            }
+            Commands::Queue(QueueArgs { command: None }) => output.item(&client.queue().await?),
+            Commands::Queue(QueueArgs {
+                command: Some(command),
+            }) => queue_command(&db, &profile, &client, output, command).await,
+            Commands::Status => output.item(&client.playback_state().await?),
+            Commands::Seek { position } => {
+                let current = client.get_currently_playing().await?;
+                let position = position.resolve(current.progress_ms, current.song.duration_ms)?;
+                client.seek(position).await?;
+                output.done(
+                    "seek",
+                    &format!("Seeked to {}.", template::format_duration(position)),
+                )
+            }
+            Commands::Volume { change } => {
+                // Only a step needs the current level; setting one works even with nothing playing.
+                let volume = match change {
+                    VolumeChange::Set(volume) => volume,
+                    VolumeChange::By(_) => change.apply(current_volume(&client).await?),
+                };
+                client.volume(volume).await?;
+                db::take_muted_volume(&db, &profile).await?;
+                output.done("volume", &format!("Volume {}%.", volume.percent()))
+            }
+            Commands::Mute => {
+                let current = current_volume(&client).await?;
+                if current == 0 {
+                    return output.done("mute", "Already muted.");
+                }
+                client.volume(Volume::new(0)?).await?;
+                db::set_muted_volume(&db, &profile, current).await?;
+                output.done("mute", &format!("Muted, was {current}%."))
+            }
+            Commands::Unmute => {
+                let Some(previous) = db::take_muted_volume(&db, &profile).await? else {
+                    return Err(SptError::InvalidInput(
+                        "not muted by spt, set a volume with `spt volume` instead".to_string(),
+                    ));
+                };
+                client.volume(Volume::new(previous)?).await?;
+                output.done("unmute", &format!("Volume {previous}%."))
+            }
+            Commands::Devices => output.list(&client.devices().await?),
+            Commands::Transfer { device, play } => {
+                let (id, name) = resolve_device(&client, &device).await?;
+                client.transfer(&id, play).await?;
+                output.done("transfer", &format!("Playing on {name}."))
+            }
+            Commands::Auth(_) => output.done("login", ""),
+            Commands::Profile(_) => Ok(()),
+        }
+    }
+    .await;
+    match result {
+        // Spotify refuses what a token lacks the scope for with a bare 403, which may as well
+        // be a restriction on the player, so its message is kept.
+        Err(SptError::Api {
+            status: 403,
+            message,
+        }) if !missing.is_empty() => Err(SptError::MissingScopes {
+            scopes: missing.join(" "),
+            message,
+        }),
+        result => result,
+    }
 }
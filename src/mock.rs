@@ -0,0 +1,1101 @@
+//! An in-memory stand-in for the Spotify Web API and accounts service.
+//!
+//! Point `SPT_API_URL` at `http://<addr>/v1` and `SPT_ACCOUNTS_URL` at `http://<addr>` to run
+//! `spt` against it without network access.
+
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Bytes},
+    App, HttpRequest, HttpResponse, HttpServer,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, collections::VecDeque, sync::Mutex};
+
+use crate::models::{
+    all_playlists, currently_playing, devices, library, paging::Paging, player, playlist, queue,
+    search,
+};
+
+#[derive(Debug, Clone)]
+pub struct MockTrack {
+    pub id: String,
+    pub name: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_ms: i64,
+}
+
+impl MockTrack {
+    fn new(id: &str, name: &str, artist: &str, album: &str, duration_ms: i64) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            artist: artist.to_string(),
+            album: album.to_string(),
+            duration_ms,
+        }
+    }
+
+    pub fn uri(&self) -> String {
+        format!("spotify:track:{}", self.id)
+    }
+
+    pub fn album_uri(&self) -> String {
+        format!("spotify:album:{}", slug(&self.album))
+    }
+
+    pub fn artist_uri(&self) -> String {
+        format!("spotify:artist:{}", slug(&self.artist))
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.name, &self.artist, &self.album]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+/// A stand-in id for an album or artist, which the mock only knows by name.
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct MockPlaylist {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub tracks: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+}
+
+impl MockDevice {
+    fn new(id: &str, name: &str, kind: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            kind: kind.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockPlayer {
+    pub devices: Vec<MockDevice>,
+    /// Id of the device playback is on, if any.
+    pub device: Option<String>,
+    pub current: Option<String>,
+    /// URI of the playlist playback started from, if any.
+    pub context: Option<String>,
+    pub progress_ms: i64,
+    pub is_playing: bool,
+    pub shuffle: bool,
+    pub repeat: String,
+    pub volume: u8,
+    pub queue: VecDeque<String>,
+    pub history: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockState {
+    pub tracks: Vec<MockTrack>,
+    pub playlists: Vec<MockPlaylist>,
+    pub player: MockPlayer,
+    /// URIs of the tracks in Liked Songs, most recently added first.
+    pub liked: Vec<String>,
+    /// Access tokens answered with 401, as Spotify does once they expire.
+    pub expired_tokens: Vec<String>,
+    /// How many of the next requests to answer with 429 and `Retry-After: 1`.
+    pub throttle: u32,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        let tracks = vec![
+            MockTrack::new("mock0001", "Airbag", "Radiohead", "OK Computer", 284_000),
+            MockTrack::new(
+                "mock0002",
+                "Paranoid Android",
+                "Radiohead",
+                "OK Computer",
+                387_000,
+            ),
+            MockTrack::new(
+                "mock0003",
+                "Karma Police",
+                "Radiohead",
+                "OK Computer",
+                264_000,
+            ),
+            MockTrack::new("mock0004", "Nude", "Radiohead", "In Rainbows", 255_000),
+            MockTrack::new("mock0005", "Hyperballad", "Björk", "Post", 321_000),
+            MockTrack::new("mock0006", "Army of Me", "Björk", "Post", 234_000),
+            MockTrack::new(
+                "mock0007",
+                "Teardrop",
+                "Massive Attack",
+                "Mezzanine",
+                330_000,
+            ),
+            MockTrack::new("mock0008", "Angel", "Massive Attack", "Mezzanine", 379_000),
+        ];
+        let playlists = vec![
+            MockPlaylist {
+                id: "mockpl01".to_string(),
+                name: "songs".to_string(),
+                owner: "mock".to_string(),
+                tracks: vec![tracks[0].uri(), tracks[4].uri()],
+            },
+            MockPlaylist {
+                id: "mockpl02".to_string(),
+                name: "trip hop".to_string(),
+                owner: "mock".to_string(),
+                tracks: vec![tracks[6].uri(), tracks[7].uri()],
+            },
+        ];
+        let player = MockPlayer {
+            devices: vec![
+                MockDevice::new("mockdev1", "Kitchen speaker", "Speaker"),
+                MockDevice::new("mockdev2", "Work laptop", "Computer"),
+                MockDevice::new("mockdev3", "Phone", "Smartphone"),
+            ],
+            device: Some("mockdev2".to_string()),
+            current: Some(tracks[0].uri()),
+            context: Some("spotify:playlist:mockpl01".to_string()),
+            progress_ms: 42_000,
+            is_playing: true,
+            repeat: "off".to_string(),
+            volume: 50,
+            ..Default::default()
+        };
+        Self {
+            liked: vec![tracks[3].uri(), tracks[5].uri()],
+            expired_tokens: vec![],
+            throttle: 0,
+            tracks,
+            playlists,
+            player,
+        }
+    }
+}
+
+impl MockState {
+    fn track(&self, uri: &str) -> Option<&MockTrack> {
+        self.tracks.iter().find(|t| t.uri() == uri)
+    }
+
+    /// The tracks a playlist, album or artist URI plays, in order.
+    fn context_tracks(&self, uri: &str) -> Option<Vec<String>> {
+        let mut parts = uri.split(':').skip(1);
+        let tracks: Vec<String> = match (parts.next()?, parts.next()?) {
+            ("playlist", id) => self.playlists.iter().find(|p| p.id == id)?.tracks.clone(),
+            ("album", _) => self
+                .tracks
+                .iter()
+                .filter(|t| t.album_uri() == uri)
+                .map(MockTrack::uri)
+                .collect(),
+            ("artist", _) => self
+                .tracks
+                .iter()
+                .filter(|t| t.artist_uri() == uri)
+                .map(MockTrack::uri)
+                .collect(),
+            _ => return None,
+        };
+        (!tracks.is_empty()).then_some(tracks)
+    }
+
+    fn playlist_mut(&mut self, id: &str) -> Option<&mut MockPlaylist> {
+        self.playlists.iter_mut().find(|p| p.id == id)
+    }
+
+    /// Moves playback to `device_id` if one was given, failing like Spotify for unknown ids.
+    fn target(&mut self, device_id: Option<&str>) -> Result<(), HttpResponse> {
+        let Some(id) = device_id else {
+            return Ok(());
+        };
+        if !self.player.devices.iter().any(|d| d.id == id) {
+            return Err(error(404, "Device not found"));
+        }
+        self.player.device = Some(id.to_string());
+        Ok(())
+    }
+
+    fn play(&mut self, uri: String) {
+        if let Some(current) = self.player.current.replace(uri) {
+            self.player.history.push(current);
+        }
+        self.player.progress_ms = 0;
+        self.player.is_playing = true;
+    }
+}
+
+type State = web::Data<Mutex<MockState>>;
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    let status = actix_web::http::StatusCode::from_u16(status).unwrap();
+    HttpResponse::build(status).json(json!({
+        "error": { "status": status.as_u16(), "message": message }
+    }))
+}
+
+/// What the mock answers instead of the endpoint: 401 without a token or with an expired one,
+/// and 429 while it is throttling.
+fn refused(req: &HttpRequest, state: &State) -> Option<HttpResponse> {
+    let bearer = req
+        .headers()
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    let mut state = state.lock().unwrap();
+    match bearer {
+        None => Some(error(401, "No token provided")),
+        Some(bearer) if state.expired_tokens.iter().any(|t| t == bearer) => {
+            Some(error(401, "The access token expired"))
+        }
+        Some(_) if state.throttle > 0 => {
+            state.throttle -= 1;
+            let mut res = error(429, "API rate limit exceeded");
+            res.headers_mut().insert(
+                actix_web::http::header::RETRY_AFTER,
+                actix_web::http::header::HeaderValue::from_static("1"),
+            );
+            Some(res)
+        }
+        Some(_) => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+/// Slices `items` the way Spotify does, linking `next`/`previous` back to this endpoint.
+fn page<T>(req: &HttpRequest, query: &PageQuery, items: Vec<T>) -> Paging<T> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0);
+    let total = items.len();
+    let info = req.connection_info();
+    let link = |offset: usize| {
+        format!(
+            "{}://{}{}?offset={}&limit={}",
+            info.scheme(),
+            info.host(),
+            req.path(),
+            offset,
+            limit
+        )
+    };
+    Paging {
+        href: link(offset),
+        next: (offset + limit < total).then(|| link(offset + limit)),
+        previous: (offset > 0).then(|| link(offset.saturating_sub(limit))),
+        items: items.into_iter().skip(offset).take(limit).collect(),
+        limit: limit as i64,
+        offset: offset as i64,
+        total: total as i64,
+    }
+}
+
+fn track_item(track: &MockTrack) -> search::Item {
+    search::Item {
+        album: search::Album {
+            name: track.album.clone(),
+            ..Default::default()
+        },
+        artists: vec![search::Artist {
+            name: track.artist.clone(),
+            ..Default::default()
+        }],
+        duration_ms: track.duration_ms,
+        id: track.id.clone(),
+        name: track.name.clone(),
+        uri: track.uri(),
+        type_field: "track".to_string(),
+        is_playable: true,
+        ..Default::default()
+    }
+}
+
+#[post("/api/token")]
+async fn token(req: HttpRequest, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+    let basic_auth = req.headers().contains_key("authorization");
+    if !basic_auth && !form.contains_key("client_id") {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_client" }));
+    }
+    let mut body = json!({
+        "access_token": "mock-access-token",
+        "token_type": "Bearer",
+        "scope": crate::auth::SCOPE,
+        "expires_in": 3600,
+    });
+    match form.get("grant_type").map(String::as_str) {
+        Some("authorization_code") | Some("refresh_token") => {
+            body["refresh_token"] = json!("mock-refresh-token");
+            HttpResponse::Ok().json(body)
+        }
+        _ => HttpResponse::BadRequest().json(json!({ "error": "unsupported_grant_type" })),
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    redirect_uri: String,
+    state: Option<String>,
+}
+
+#[get("/authorize")]
+async fn authorize(query: web::Query<AuthorizeQuery>) -> HttpResponse {
+    let mut location = format!("{}?code=mock-code", query.redirect_uri);
+    if let Some(state) = &query.state {
+        location.push_str(&format!("&state={}", state));
+    }
+    HttpResponse::Found()
+        .insert_header(("location", location))
+        .finish()
+}
+
+#[get("/v1/me/playlists")]
+async fn my_playlists(
+    req: HttpRequest,
+    query: web::Query<PageQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let items: Vec<all_playlists::Item> = state
+        .playlists
+        .iter()
+        .map(|p| all_playlists::Item {
+            id: p.id.clone(),
+            name: p.name.clone(),
+            owner: all_playlists::Owner {
+                display_name: p.owner.clone(),
+                id: p.owner.clone(),
+                ..Default::default()
+            },
+            tracks: all_playlists::Tracks {
+                total: p.tracks.len() as i64,
+                ..Default::default()
+            },
+            uri: format!("spotify:playlist:{}", p.id),
+            type_field: "playlist".to_string(),
+            ..Default::default()
+        })
+        .collect();
+    HttpResponse::Ok().json(page(&req, &query, items))
+}
+
+#[get("/v1/playlists/{id}/tracks")]
+async fn playlist_tracks(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let Some(pl) = state.playlists.iter().find(|p| p.id == *path) else {
+        return error(404, "Not found.");
+    };
+    let items = pl
+        .tracks
+        .iter()
+        .filter_map(|uri| state.track(uri))
+        .map(|t| playlist::Item {
+            track: Some(playlist::Track {
+                album: playlist::Album {
+                    name: t.album.clone(),
+                },
+                artists: vec![playlist::Artist {
+                    name: t.artist.clone(),
+                }],
+                name: t.name.clone(),
+                id: Some(t.id.clone()),
+                uri: t.uri(),
+                duration_ms: t.duration_ms,
+            }),
+        })
+        .collect();
+    HttpResponse::Ok().json(page(&req, &query, items))
+}
+
+#[derive(Deserialize)]
+struct UrisBody {
+    uris: Vec<String>,
+}
+
+#[post("/v1/playlists/{id}/tracks")]
+async fn add_tracks(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<UrisBody>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    let Some(pl) = state.playlist_mut(&path) else {
+        return error(404, "Not found.");
+    };
+    pl.tracks.extend(body.into_inner().uris);
+    HttpResponse::Created().json(json!({ "snapshot_id": format!("mock-{}", pl.tracks.len()) }))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    limit: Option<usize>,
+}
+
+/// The first `limit` of `items`, without repeats of the same album or artist.
+fn search_page<T: PartialEq + Default>(mut items: Vec<T>, limit: usize) -> Paging<T> {
+    items.dedup();
+    Paging {
+        total: items.len() as i64,
+        items: items.into_iter().take(limit).collect(),
+        limit: limit as i64,
+        ..Default::default()
+    }
+}
+
+#[get("/v1/search")]
+async fn search_tracks(
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let matches: Vec<&MockTrack> = state
+        .tracks
+        .iter()
+        .filter(|t| t.matches(&query.q))
+        .collect();
+    let limit = query.limit.unwrap_or(20);
+    let kinds = query.kind.as_deref().unwrap_or("track");
+    let mut res = search::SpotifySearchRes::default();
+    for kind in kinds.split(',') {
+        match kind {
+            "track" => {
+                res.tracks = search_page(matches.iter().copied().map(track_item).collect(), limit)
+            }
+            "album" => {
+                res.albums = search_page(
+                    matches
+                        .iter()
+                        .map(|t| search::Album {
+                            name: t.album.clone(),
+                            uri: t.album_uri(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    limit,
+                )
+            }
+            "artist" => {
+                res.artists = search_page(
+                    matches
+                        .iter()
+                        .map(|t| search::Artist {
+                            name: t.artist.clone(),
+                            uri: t.artist_uri(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    limit,
+                )
+            }
+            _ => return error(400, "Unsupported type"),
+        }
+    }
+    HttpResponse::Ok().json(res)
+}
+
+#[get("/v1/me/player/currently-playing")]
+async fn player_currently_playing(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let Some(track) = state
+        .player
+        .current
+        .as_deref()
+        .and_then(|uri| state.track(uri))
+    else {
+        return HttpResponse::NoContent().finish();
+    };
+    HttpResponse::Ok().json(currently_playing::SpotifyCurrentlyPlayingRes {
+        progress_ms: Some(state.player.progress_ms),
+        is_playing: state.player.is_playing,
+        currently_playing_type: "track".to_string(),
+        item: Some(playing_item(track)),
+        ..Default::default()
+    })
+}
+
+fn playing_item(track: &MockTrack) -> currently_playing::PlayingItem {
+    currently_playing::PlayingItem::Track(Box::new(track_object(track)))
+}
+
+fn track_object(track: &MockTrack) -> currently_playing::Item {
+    currently_playing::Item {
+        album: currently_playing::Album {
+            name: track.album.clone(),
+            ..Default::default()
+        },
+        artists: vec![currently_playing::Artist {
+            name: track.artist.clone(),
+            ..Default::default()
+        }],
+        duration_ms: track.duration_ms,
+        id: Some(track.id.clone()),
+        name: track.name.clone(),
+        uri: track.uri(),
+        is_playable: true,
+        ..Default::default()
+    }
+}
+
+#[get("/v1/me/player")]
+async fn player_state(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let Some(device) = state
+        .player
+        .device
+        .as_deref()
+        .and_then(|id| state.player.devices.iter().find(|d| d.id == id))
+    else {
+        return HttpResponse::NoContent().finish();
+    };
+    HttpResponse::Ok().json(player::SpotifyPlayerRes {
+        device: devices::Device {
+            id: Some(device.id.clone()),
+            is_active: true,
+            name: device.name.clone(),
+            type_field: device.kind.clone(),
+            volume_percent: Some(state.player.volume.into()),
+            supports_volume: true,
+            ..Default::default()
+        },
+        repeat_state: state.player.repeat.clone(),
+        shuffle_state: state.player.shuffle,
+        context: state
+            .player
+            .context
+            .clone()
+            .map(|uri| currently_playing::Context {
+                type_field: uri.split(':').nth(1).unwrap_or_default().to_string(),
+                uri,
+                ..Default::default()
+            }),
+        progress_ms: Some(state.player.progress_ms),
+        is_playing: state.player.is_playing,
+        item: state
+            .player
+            .current
+            .as_deref()
+            .and_then(|uri| state.track(uri))
+            .map(playing_item),
+        currently_playing_type: "track".to_string(),
+        ..Default::default()
+    })
+}
+
+#[get("/v1/playlists/{id}")]
+async fn playlist_info(req: HttpRequest, path: web::Path<String>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    match state.playlists.iter().find(|p| p.id == *path) {
+        Some(pl) => HttpResponse::Ok().json(player::Named {
+            name: pl.name.clone(),
+        }),
+        None => error(404, "Not found."),
+    }
+}
+
+#[get("/v1/tracks/{id}")]
+async fn track_info(req: HttpRequest, path: web::Path<String>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    match state.tracks.iter().find(|t| t.id == *path) {
+        Some(track) => HttpResponse::Ok().json(playing_item(track)),
+        None => error(404, "Non existing id"),
+    }
+}
+
+#[get("/v1/{kind:albums|artists}/{id}")]
+async fn context_info(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let (kind, id) = path.into_inner();
+    let state = state.lock().unwrap();
+    let name = state.tracks.iter().find_map(|t| match kind.as_str() {
+        "albums" if slug(&t.album) == id => Some(t.album.clone()),
+        "artists" if slug(&t.artist) == id => Some(t.artist.clone()),
+        _ => None,
+    });
+    match name {
+        Some(name) => HttpResponse::Ok().json(player::Named { name }),
+        None => error(404, "Not found."),
+    }
+}
+
+#[post("/v1/me/player/next")]
+async fn next(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    let next = match state.player.queue.pop_front() {
+        Some(uri) => uri,
+        None => {
+            let position = state
+                .player
+                .current
+                .as_deref()
+                .and_then(|uri| state.tracks.iter().position(|t| t.uri() == uri))
+                .map_or(0, |i| (i + 1) % state.tracks.len());
+            state.tracks[position].uri()
+        }
+    };
+    state.play(next);
+    HttpResponse::NoContent().finish()
+}
+
+#[post("/v1/me/player/previous")]
+async fn previous(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    match state.player.history.pop() {
+        Some(uri) => {
+            if let Some(current) = state.player.current.replace(uri) {
+                state.player.queue.push_front(current);
+            }
+            state.player.progress_ms = 0;
+        }
+        None => state.player.progress_ms = 0,
+    }
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct QueueQuery {
+    uri: String,
+}
+
+#[post("/v1/me/player/queue")]
+async fn add_to_queue(
+    req: HttpRequest,
+    query: web::Query<QueueQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    if state.track(&query.uri).is_none() {
+        return error(400, "Invalid track uri");
+    }
+    state.player.queue.push_back(query.into_inner().uri);
+    HttpResponse::NoContent().finish()
+}
+
+#[get("/v1/me/player/queue")]
+async fn player_queue(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let item = |uri: &String| state.track(uri).map(playing_item);
+    HttpResponse::Ok().json(queue::SpotifyQueueRes {
+        currently_playing: state.player.current.as_ref().and_then(item),
+        queue: state.player.queue.iter().filter_map(item).collect(),
+    })
+}
+
+#[derive(Deserialize)]
+struct IdsQuery {
+    ids: String,
+}
+
+#[get("/v1/me/tracks")]
+async fn saved_tracks(
+    req: HttpRequest,
+    query: web::Query<PageQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let items = state
+        .liked
+        .iter()
+        .filter_map(|uri| state.track(uri))
+        .map(|track| library::SavedTrack {
+            added_at: "2023-01-01T00:00:00Z".to_string(),
+            track: track_object(track),
+        })
+        .collect();
+    HttpResponse::Ok().json(page(&req, &query, items))
+}
+
+#[put("/v1/me/tracks")]
+async fn save_tracks(req: HttpRequest, query: web::Query<IdsQuery>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    for id in query.ids.split(',') {
+        let Some(uri) = state.tracks.iter().find(|t| t.id == id).map(MockTrack::uri) else {
+            return error(400, "Invalid base62 id");
+        };
+        if !state.liked.contains(&uri) {
+            state.liked.insert(0, uri);
+        }
+    }
+    HttpResponse::Ok().finish()
+}
+
+#[delete("/v1/me/tracks")]
+async fn remove_tracks(
+    req: HttpRequest,
+    query: web::Query<IdsQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    let ids: Vec<&str> = query.ids.split(',').collect();
+    state
+        .liked
+        .retain(|uri| !ids.iter().any(|id| uri.ends_with(&format!(":{id}"))));
+    HttpResponse::Ok().finish()
+}
+
+#[get("/v1/me/tracks/contains")]
+async fn tracks_contain(
+    req: HttpRequest,
+    query: web::Query<IdsQuery>,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let contains: Vec<bool> = query
+        .ids
+        .split(',')
+        .map(|id| {
+            state
+                .liked
+                .iter()
+                .any(|uri| uri.ends_with(&format!(":{id}")))
+        })
+        .collect();
+    HttpResponse::Ok().json(contains)
+}
+
+#[get("/v1/me/player/devices")]
+async fn player_devices(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let state = state.lock().unwrap();
+    let devices = state
+        .player
+        .devices
+        .iter()
+        .map(|d| {
+            let is_active = state.player.device.as_deref() == Some(d.id.as_str());
+            devices::Device {
+                id: Some(d.id.clone()),
+                is_active,
+                name: d.name.clone(),
+                type_field: d.kind.clone(),
+                volume_percent: Some(if is_active { state.player.volume } else { 100 }.into()),
+                supports_volume: true,
+                ..Default::default()
+            }
+        })
+        .collect();
+    HttpResponse::Ok().json(devices::SpotifyDevicesRes { devices })
+}
+
+#[derive(Deserialize)]
+struct TransferBody {
+    device_ids: Vec<String>,
+    #[serde(default)]
+    play: bool,
+}
+
+#[put("/v1/me/player")]
+async fn transfer(req: HttpRequest, body: web::Json<TransferBody>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let [id] = body.device_ids.as_slice() else {
+        return error(400, "Exactly one device id is supported");
+    };
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(Some(id)) {
+        return res;
+    }
+    if body.play {
+        state.player.is_playing = true;
+    }
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct DeviceQuery {
+    device_id: Option<String>,
+}
+
+#[put("/v1/me/player/pause")]
+async fn pause(req: HttpRequest, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    state.lock().unwrap().player.is_playing = false;
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct PlayBody {
+    uris: Option<Vec<String>>,
+    context_uri: Option<String>,
+    offset: Option<PlayOffset>,
+    position_ms: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct PlayOffset {
+    position: Option<usize>,
+    uri: Option<String>,
+}
+
+#[put("/v1/me/player/play")]
+async fn play(
+    req: HttpRequest,
+    query: web::Query<DeviceQuery>,
+    body: Bytes,
+    state: State,
+) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(query.device_id.as_deref()) {
+        return res;
+    }
+    if body.is_empty() {
+        state.player.is_playing = true;
+        return HttpResponse::NoContent().finish();
+    }
+    let Ok(body) = serde_json::from_slice::<PlayBody>(&body) else {
+        return error(400, "Malformed json");
+    };
+    if let Some(context) = body.context_uri {
+        let Some(tracks) = state.context_tracks(&context) else {
+            return error(404, "Not found.");
+        };
+        let start = match body.offset {
+            Some(_) if context.starts_with("spotify:artist:") => {
+                return error(400, "Can't have offset for context type: ARTIST")
+            }
+            Some(PlayOffset { uri: Some(uri), .. }) => tracks.iter().position(|t| *t == uri),
+            Some(PlayOffset {
+                position: Some(position),
+                ..
+            }) => (position < tracks.len()).then_some(position),
+            _ => Some(0),
+        };
+        let Some(start) = start else {
+            return error(400, "Invalid offset");
+        };
+        state.play(tracks[start].clone());
+        state.player.context = Some(context);
+        state.player.progress_ms = body.position_ms.unwrap_or_default();
+        return HttpResponse::NoContent().finish();
+    }
+    let mut uris = body.uris.unwrap_or_default().into_iter();
+    match uris.next() {
+        Some(first) => {
+            state.play(first);
+            state.player.context = None;
+            state.player.progress_ms = body.position_ms.unwrap_or_default();
+            state.player.queue = uris.collect();
+        }
+        None => state.player.is_playing = true,
+    }
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct StateQuery {
+    state: String,
+    device_id: Option<String>,
+}
+
+#[put("/v1/me/player/shuffle")]
+async fn shuffle(req: HttpRequest, query: web::Query<StateQuery>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(query.device_id.as_deref()) {
+        return res;
+    }
+    match query.state.parse::<bool>() {
+        Ok(shuffle) => {
+            state.player.shuffle = shuffle;
+            HttpResponse::NoContent().finish()
+        }
+        Err(_) => error(400, "Invalid state"),
+    }
+}
+
+#[put("/v1/me/player/repeat")]
+async fn repeat(req: HttpRequest, query: web::Query<StateQuery>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(query.device_id.as_deref()) {
+        return res;
+    }
+    match query.state.as_str() {
+        "track" | "context" | "off" => {
+            state.player.repeat = query.into_inner().state;
+            HttpResponse::NoContent().finish()
+        }
+        _ => error(400, "Invalid state"),
+    }
+}
+
+#[derive(Deserialize)]
+struct SeekQuery {
+    position_ms: i64,
+    device_id: Option<String>,
+}
+
+#[put("/v1/me/player/seek")]
+async fn seek(req: HttpRequest, query: web::Query<SeekQuery>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(query.device_id.as_deref()) {
+        return res;
+    }
+    if query.position_ms < 0 {
+        return error(400, "Invalid position");
+    }
+    state.player.progress_ms = query.position_ms;
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize)]
+struct VolumeQuery {
+    volume_percent: u8,
+    device_id: Option<String>,
+}
+
+#[put("/v1/me/player/volume")]
+async fn volume(req: HttpRequest, query: web::Query<VolumeQuery>, state: State) -> HttpResponse {
+    if let Some(res) = refused(&req, &state) {
+        return res;
+    }
+    if query.volume_percent > 100 {
+        return error(400, "Invalid volume");
+    }
+    let mut state = state.lock().unwrap();
+    if let Err(res) = state.target(query.device_id.as_deref()) {
+        return res;
+    }
+    state.player.volume = query.volume_percent;
+    HttpResponse::NoContent().finish()
+}
+
+/// Registers every mock endpoint, sharing `state` between them.
+pub fn configure(state: web::Data<Mutex<MockState>>) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(state)
+            .service(token)
+            .service(authorize)
+            .service(my_playlists)
+            .service(playlist_tracks)
+            .service(add_tracks)
+            .service(search_tracks)
+            .service(player_currently_playing)
+            .service(player_state)
+            .service(playlist_info)
+            .service(context_info)
+            .service(track_info)
+            .service(saved_tracks)
+            .service(save_tracks)
+            .service(remove_tracks)
+            .service(tracks_contain)
+            .service(next)
+            .service(previous)
+            .service(add_to_queue)
+            .service(player_queue)
+            .service(player_devices)
+            .service(transfer)
+            .service(pause)
+            .service(play)
+            .service(shuffle)
+            .service(repeat)
+            .service(seek)
+            .service(volume);
+    }
+}
+
+pub async fn run(host: &str, port: u16) -> std::io::Result<()> {
+    let state = web::Data::new(Mutex::new(MockState::default()));
+    HttpServer::new(move || App::new().configure(configure(state.clone())))
+        .bind((host, port))?
+        .run()
+        .await
+}
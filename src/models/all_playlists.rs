@@ -1,17 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SpotifyAllPlaylistsRes {
-    pub href: String,
-    pub items: Vec<Item>,
-    pub limit: i64,
-    pub next: Value,
-    pub offset: i64,
-    pub previous: Value,
-    pub total: i64,
-}
+use super::paging::Paging;
+
+pub type SpotifyAllPlaylistsRes = Paging<Item>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -3,13 +3,16 @@ use serde::{Deserialize, Serialize};
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SpotifyCurrentlyPlayingRes {
+    #[serde(default)]
     pub timestamp: i64,
     pub context: Option<Context>,
     #[serde(rename = "progress_ms")]
-    pub progress_ms: i64,
-    pub item: Item,
-    #[serde(rename = "currently_playing_type")]
+    pub progress_ms: Option<i64>,
+    /// `None` while an ad plays, or for an episode when `additional_types` leaves them out.
+    pub item: Option<PlayingItem>,
+    #[serde(rename = "currently_playing_type", default)]
     pub currently_playing_type: String,
+    #[serde(default)]
     pub actions: Actions,
     #[serde(rename = "is_playing")]
     pub is_playing: bool,
@@ -18,9 +21,9 @@ pub struct SpotifyCurrentlyPlayingRes {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Context {
-    #[serde(rename = "external_urls")]
+    #[serde(rename = "external_urls", default)]
     pub external_urls: ExternalUrls,
-    pub href: String,
+    pub href: Option<String>,
     #[serde(rename = "type")]
     pub type_field: String,
     pub uri: String,
@@ -29,11 +32,20 @@ pub struct Context {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExternalUrls {
-    pub spotify: String,
+    pub spotify: Option<String>,
+}
+
+/// What `item` holds, told apart by its `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PlayingItem {
+    Track(Box<Item>),
+    Episode(Box<Episode>),
 }
 
+/// A track; local files come without ids, popularity, ISRCs and most album details.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Item {
     pub album: Album,
     pub artists: Vec<Artist>,
@@ -46,85 +58,115 @@ pub struct Item {
     pub external_ids: ExternalIds,
     #[serde(rename = "external_urls")]
     pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
+    pub href: Option<String>,
+    pub id: Option<String>,
     #[serde(rename = "is_local")]
     pub is_local: bool,
     #[serde(rename = "is_playable")]
     pub is_playable: bool,
     pub name: String,
-    pub popularity: i64,
+    pub popularity: Option<i64>,
     #[serde(rename = "preview_url")]
-    pub preview_url: String,
+    pub preview_url: Option<String>,
     #[serde(rename = "track_number")]
     pub track_number: i64,
-    #[serde(rename = "type")]
-    pub type_field: String,
     pub uri: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
+pub struct Episode {
+    pub description: String,
+    #[serde(rename = "duration_ms")]
+    pub duration_ms: i64,
+    pub explicit: bool,
+    #[serde(rename = "external_urls")]
+    pub external_urls: ExternalUrls,
+    pub href: Option<String>,
+    pub id: Option<String>,
+    pub images: Vec<Image>,
+    #[serde(rename = "is_playable")]
+    pub is_playable: bool,
+    pub name: String,
+    #[serde(rename = "release_date")]
+    pub release_date: Option<String>,
+    pub show: Show,
+    pub uri: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Show {
+    pub id: Option<String>,
+    pub name: String,
+    pub publisher: String,
+    pub uri: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Album {
     #[serde(rename = "album_group")]
     pub album_group: Option<String>,
     #[serde(rename = "album_type")]
-    pub album_type: String,
+    pub album_type: Option<String>,
     pub artists: Vec<Artist>,
     #[serde(rename = "external_urls")]
     pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
+    pub href: Option<String>,
+    pub id: Option<String>,
     pub images: Vec<Image>,
     #[serde(rename = "is_playable")]
-    pub is_playable: bool,
+    pub is_playable: Option<bool>,
     pub name: String,
     #[serde(rename = "release_date")]
-    pub release_date: String,
+    pub release_date: Option<String>,
     #[serde(rename = "release_date_precision")]
-    pub release_date_precision: String,
+    pub release_date_precision: Option<String>,
     #[serde(rename = "total_tracks")]
-    pub total_tracks: i64,
+    pub total_tracks: Option<i64>,
     #[serde(rename = "type")]
-    pub type_field: String,
-    pub uri: String,
+    pub type_field: Option<String>,
+    pub uri: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Artist {
     #[serde(rename = "external_urls")]
     pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
+    pub href: Option<String>,
+    pub id: Option<String>,
     pub name: String,
     #[serde(rename = "type")]
-    pub type_field: String,
-    pub uri: String,
+    pub type_field: Option<String>,
+    pub uri: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Image {
-    pub height: i64,
+    pub height: Option<i64>,
     pub url: String,
-    pub width: i64,
+    pub width: Option<i64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExternalIds {
-    pub isrc: String,
+    pub isrc: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Actions {
+    #[serde(default)]
     pub disallows: Disallows,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Disallows {
+    #[serde(default)]
     pub resuming: bool,
 }
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifyDevicesRes {
+    pub devices: Vec<Device>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    /// Missing for devices that cannot be controlled through the API.
+    pub id: Option<String>,
+    #[serde(rename = "is_active")]
+    pub is_active: bool,
+    #[serde(rename = "is_private_session", default)]
+    pub is_private_session: bool,
+    #[serde(rename = "is_restricted", default)]
+    pub is_restricted: bool,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(rename = "volume_percent")]
+    pub volume_percent: Option<i64>,
+    #[serde(rename = "supports_volume", default)]
+    pub supports_volume: bool,
+}
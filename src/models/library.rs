@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use super::currently_playing::Item;
+
+/// An entry of the user's Liked Songs.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedTrack {
+    #[serde(rename = "added_at", default)]
+    pub added_at: String,
+    pub track: Item,
+}
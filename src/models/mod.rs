@@ -5,3 +5,13 @@ pub mod playlist;
 pub mod currently_playing;
 
 pub mod search;
+
+pub mod paging;
+
+pub mod devices;
+
+pub mod player;
+
+pub mod queue;
+
+pub mod library;
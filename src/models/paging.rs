@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Spotify's paging object, wrapping every list endpoint that supports `limit`/`offset`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Paging<T> {
+    #[serde(default)]
+    pub href: String,
+    pub items: Vec<T>,
+    #[serde(default)]
+    pub limit: i64,
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub offset: i64,
+    #[serde(default)]
+    pub previous: Option<String>,
+    #[serde(default)]
+    pub total: i64,
+}
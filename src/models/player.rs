@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use super::currently_playing::{Context, PlayingItem};
+use super::devices::Device;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifyPlayerRes {
+    pub device: Device,
+    #[serde(rename = "repeat_state")]
+    pub repeat_state: String,
+    #[serde(rename = "shuffle_state")]
+    pub shuffle_state: bool,
+    pub context: Option<Context>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(rename = "progress_ms")]
+    pub progress_ms: Option<i64>,
+    #[serde(rename = "is_playing")]
+    pub is_playing: bool,
+    pub item: Option<PlayingItem>,
+    #[serde(rename = "currently_playing_type", default)]
+    pub currently_playing_type: String,
+}
+
+/// Just the name of a playlist, album or artist.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Named {
+    pub name: String,
+}
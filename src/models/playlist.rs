@@ -1,21 +1,20 @@
 use serde::{Deserialize, Serialize};
 
+use super::paging::Paging;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SpotifyPlaylistRes {
     pub tracks: Tracks,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Tracks {
-    pub items: Vec<Item>,
-}
+pub type Tracks = Paging<Item>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Item {
-    pub track: Track,
+    /// `null` for tracks that were removed from Spotify or are unavailable.
+    pub track: Option<Track>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -24,6 +23,13 @@ pub struct Track {
     pub album: Album,
     pub artists: Vec<Artist>,
     pub name: String,
+    /// `null` for local files.
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub uri: String,
+    #[serde(rename = "duration_ms", default)]
+    pub duration_ms: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use super::currently_playing::PlayingItem;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifyQueueRes {
+    #[serde(rename = "currently_playing")]
+    pub currently_playing: Option<PlayingItem>,
+    #[serde(default)]
+    pub queue: Vec<PlayingItem>,
+}
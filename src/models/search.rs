@@ -1,23 +1,20 @@
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+
+use super::paging::Paging;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SpotifySearchRes {
+    /// Each list is only there when its type was asked for.
+    #[serde(default)]
     pub tracks: Tracks,
+    #[serde(default)]
+    pub albums: Paging<Album>,
+    #[serde(default)]
+    pub artists: Paging<Artist>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Tracks {
-    pub href: String,
-    pub items: Vec<Item>,
-    pub limit: i64,
-    pub next: String,
-    pub offset: i64,
-    pub previous: Value,
-    pub total: i64,
-}
+pub type Tracks = Paging<Item>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -63,7 +60,7 @@ pub struct Album {
     pub href: String,
     pub id: String,
     pub images: Vec<Image>,
-    #[serde(rename = "is_playable")]
+    #[serde(rename = "is_playable", default)]
     pub is_playable: bool,
     pub name: String,
     #[serde(rename = "release_date")]
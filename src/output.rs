@@ -0,0 +1,236 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt::Display;
+
+use playlist::config::FormatConfig;
+use playlist::error::{Result, SptError};
+use playlist::spotify::{
+    CurrentlyPlaying, Device, PlaybackState, Playlist, Queue, QueuedSong, Song,
+};
+use playlist::template::Template;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON document per command
+    Json,
+    /// One JSON object per line, for lists that are streamed into other tools
+    Ndjson,
+}
+
+/// The text-mode templates for each kind of item; `None` keeps its usual line.
+#[derive(Debug, Clone, Default)]
+pub struct Templates {
+    pub track: Option<Template>,
+    pub playlist: Option<Template>,
+    pub current: Option<Template>,
+}
+
+impl Templates {
+    /// The `[format]` config table, with `--format` replacing every entry when given.
+    pub fn load(config: &FormatConfig, flag: Option<&str>) -> Result<Self> {
+        if let Some(flag) = flag {
+            let template = Some(Template::parse(flag)?);
+            return Ok(Self {
+                track: template.clone(),
+                playlist: template.clone(),
+                current: template,
+            });
+        }
+        let parse = |name: &str, src: &Option<String>| {
+            src.as_deref()
+                .map(Template::parse)
+                .transpose()
+                .map_err(|e| SptError::Config(format!("format.{name}: {e}")))
+        };
+        let track = parse("track", &config.track)?;
+        Ok(Self {
+            current: parse("current", &config.current)?.or_else(|| track.clone()),
+            playlist: parse("playlist", &config.playlist)?,
+            track,
+        })
+    }
+}
+
+/// How an item looks in text mode.
+pub trait Render: Serialize + Display {
+    fn render(&self, _templates: &Templates) -> String {
+        self.to_string()
+    }
+}
+
+impl Render for Song {
+    fn render(&self, templates: &Templates) -> String {
+        match &templates.track {
+            Some(template) => template.render(self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Render for CurrentlyPlaying {
+    fn render(&self, templates: &Templates) -> String {
+        match &templates.current {
+            Some(template) => template.render(self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Render for Playlist {
+    fn render(&self, templates: &Templates) -> String {
+        match &templates.playlist {
+            Some(template) => template.render(self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Render for PlaybackState {
+    fn render(&self, templates: &Templates) -> String {
+        match &templates.current {
+            Some(template) => template.render(self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Render for Queue {
+    fn render(&self, templates: &Templates) -> String {
+        let mut out = match &self.currently_playing {
+            Some(song) => format!("Now playing: {}", song.render(templates)),
+            None => "Now playing: nothing".to_string(),
+        };
+        if self.queue.is_empty() {
+            out.push_str("\nUp next: nothing");
+        } else {
+            out.push_str("\nUp next:");
+            for queued in &self.queue {
+                out.push('\n');
+                out.push_str(&queued.render(templates));
+            }
+        }
+        out
+    }
+}
+
+impl Render for QueuedSong {
+    fn render(&self, templates: &Templates) -> String {
+        format!("{}. {}", self.position, self.song.render(templates))
+    }
+}
+
+impl Render for Device {}
+
+/// Prints command results in the format picked with `--output`.
+///
+/// Text mode keeps spt's usual lines, or the configured templates; the JSON modes emit the
+/// documented schema on stdout and move everything meant for a human (prompts, notes) to stderr.
+#[derive(Debug, Clone)]
+pub struct Output {
+    format: OutputFormat,
+    templates: Templates,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            templates: Templates::default(),
+        }
+    }
+
+    pub fn with_templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    /// A single result.
+    pub fn item<T: Render>(&self, item: &T) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => println!("{}", item.render(&self.templates)),
+            OutputFormat::Json => println!("{}", to_json(item, true)?),
+            OutputFormat::Ndjson => println!("{}", to_json(item, false)?),
+        }
+        Ok(())
+    }
+
+    /// A list of results: one per line, a JSON array, or one JSON object per line.
+    pub fn list<T: Render>(&self, items: &[T]) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => items
+                .iter()
+                .for_each(|item| println!("{}", item.render(&self.templates))),
+            OutputFormat::Json => println!("{}", to_json(&items, true)?),
+            OutputFormat::Ndjson => {
+                for item in items {
+                    println!("{}", to_json(item, false)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`list`](Self::list), but numbered in text mode so the user can pick an entry.
+    pub fn numbered<T: Render>(&self, items: &[T]) -> Result<()> {
+        if self.is_text() {
+            for (i, item) in items.iter().enumerate() {
+                println!("{}. {}", i + 1, item.render(&self.templates));
+            }
+            return Ok(());
+        }
+        self.list(items)
+    }
+
+    /// Reports that `action` succeeded, with `message` for humans.
+    pub fn done(&self, action: &str, message: &str) -> Result<()> {
+        match self.format {
+            OutputFormat::Text if message.is_empty() => (),
+            OutputFormat::Text => println!("{message}"),
+            OutputFormat::Json | OutputFormat::Ndjson => println!(
+                "{}",
+                to_json(&json!({ "ok": true, "action": action }), false)?
+            ),
+        }
+        Ok(())
+    }
+
+    /// Progress meant for the user; kept off stdout in the JSON modes.
+    pub fn note(&self, message: &str) {
+        if self.is_text() {
+            println!("{message}");
+        } else {
+            eprintln!("{message}");
+        }
+    }
+
+    /// Reports a failed command on stderr.
+    pub fn error(&self, e: &SptError) {
+        if self.is_text() {
+            eprintln!("spt: {e}");
+            return;
+        }
+        let error = json!({ "error": { "code": e.exit_code(), "message": e.to_string() } });
+        eprintln!("{error}");
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T, pretty: bool) -> Result<String> {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    Ok(json.map_err(std::io::Error::from)?)
+}
@@ -0,0 +1,57 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+/// How a [`SpotifyClient`](crate::spotify::SpotifyClient) retries throttled and failed requests.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries per request for 429 responses, and 5xx ones to idempotent methods, before
+    /// giving up.
+    pub max_retries: u32,
+    /// Delay before the first 5xx retry, doubled on every further attempt.
+    pub base_delay: Duration,
+    /// Upper bound for the exponential 5xx backoff.
+    pub max_delay: Duration,
+    /// Longest `Retry-After` the client will sleep through; longer waits surface as
+    /// [`SptError::RateLimited`](crate::error::SptError::RateLimited).
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Capped exponential backoff for `attempt` (starting at 0), with the upper half jittered.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds, the only form Spotify sends.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
@@ -1,330 +1,1304 @@
-use super::{Playlist, Song, SpotifyAccessToken};
-use playlist::models::{
-    all_playlists::SpotifyAllPlaylistsRes, currently_playing::SpotifyCurrentlyPlayingRes,
-    playlist::SpotifyPlaylistRes, search::SpotifySearchRes,
+//! The Spotify Web API client and the types spt shows.
+//!
+//! The serialized form of the types returned here is spt's `--output json` schema (see the
+//! README), so renaming or removing a field is a breaking change.
+
+use crate::auth::SpotifyAccessToken;
+use crate::error::{check, Result, SptError};
+use crate::models::{
+    all_playlists,
+    currently_playing::{PlayingItem, SpotifyCurrentlyPlayingRes},
+    devices::{self, SpotifyDevicesRes},
+    library::SavedTrack,
+    paging::Paging,
+    player::{Named, SpotifyPlayerRes},
+    playlist,
+    queue::SpotifyQueueRes,
+    search::SpotifySearchRes,
 };
+use crate::retry::{retry_after, RetryPolicy};
+use crate::uri::{SpotifyUri, UriKind};
+use futures::{stream, Stream, TryStreamExt};
 use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
-use serde::{Deserialize, Serialize};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt::Display, future::Future, pin::Pin, str::FromStr, sync::Arc};
+use tokio::sync::Semaphore;
+
+pub const DEFAULT_API_URL: &str = "https://api.spotify.com/v1";
+
+/// Requests a single client keeps in flight at once unless configured otherwise.
+pub const DEFAULT_CONCURRENCY_LIMIT: usize = 4;
+
+/// Spotify accepts at most this many URIs per playlist insert.
+const MAX_URIS_PER_REQUEST: usize = 100;
+
+/// Where [`SpotifyClient::paginate`] fetches its next page from.
+enum Cursor {
+    First(String, Vec<(&'static str, String)>),
+    Next(String),
+    Done,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SpotifyJSON {
     uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position_ms: Option<i64>,
 }
 
-pub async fn get_all_playlists(
-    spotify_token: SpotifyAccessToken,
-) -> Result<Vec<Playlist>, anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/playlists";
+#[derive(Debug, Serialize)]
+struct ContextJSON<'a> {
+    context_uri: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<OffsetJSON<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position_ms: Option<i64>,
+}
 
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .bearer_auth(spotify_token.access_token)
-        .send()
-        .await?
-        .json::<SpotifyAllPlaylistsRes>()
-        .await?;
+/// `{"position": 0}` or `{"uri": "spotify:track:..."}`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OffsetJSON<'a> {
+    Position(usize),
+    Uri(&'a str),
+}
 
-    let mut playlists: Vec<Playlist> = vec![];
-    for playlist in res.items {
-        playlists.push(Playlist {
-            name: playlist.name,
-            owner: playlist.owner.display_name,
-            id: playlist.id,
-        })
+#[derive(Debug, Serialize)]
+struct TransferJSON<'a> {
+    device_ids: [&'a str; 1],
+    play: bool,
+}
+
+/// A playlist as spt shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Playlist {
+    pub id: String,
+    pub uri: String,
+    pub name: String,
+    pub owner: String,
+    pub tracks: i64,
+}
+
+impl Display for Playlist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} | {}", self.name, self.owner)
     }
+}
 
-    Ok(playlists)
-}
-
-pub async fn get_playlist(
-    token: SpotifyAccessToken,
-    id: String,
-) -> Result<Vec<Song>, anyhow::Error> {
-    let url = format!("https://api.spotify.com/v1/playlists/{}", id);
-
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .bearer_auth(token.access_token)
-        .query(&[
-            ("market", "US"),
-            (
-                "fields",
-                "tracks.items(track(name, artists(name), album(name)))",
-            ),
-        ])
-        .send()
-        .await?
-        .json::<SpotifyPlaylistRes>()
-        .await?;
+/// A track as spt shows it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Song {
+    pub id: String,
+    pub uri: String,
+    pub name: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_ms: i64,
+}
+
+impl Display for Song {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} | {} | {}", self.name, self.artist, self.album)
+    }
+}
+
+/// A track waiting in the queue, numbered from 1 for the one that plays next.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedSong {
+    pub position: usize,
+    #[serde(flatten)]
+    pub song: Song,
+}
+
+impl Display for QueuedSong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}. {}", self.position, self.song)
+    }
+}
+
+/// The current track and what plays after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Queue {
+    pub currently_playing: Option<Song>,
+    pub queue: Vec<QueuedSong>,
+}
+
+impl Display for Queue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.currently_playing {
+            Some(song) => write!(f, "Now playing: {song}")?,
+            None => write!(f, "Now playing: nothing")?,
+        }
+        if self.queue.is_empty() {
+            return write!(f, "\nUp next: nothing");
+        }
+        write!(f, "\nUp next:")?;
+        for queued in &self.queue {
+            write!(f, "\n{queued}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the player repeats once the current track ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatState {
+    Track,
+    Context,
+    Off,
+}
+
+impl RepeatState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatState::Track => "track",
+            RepeatState::Context => "context",
+            RepeatState::Off => "off",
+        }
+    }
+
+    /// Reads Spotify's `repeat_state`, treating anything unknown as off.
+    fn from_api(state: &str) -> Self {
+        match state {
+            "track" => RepeatState::Track,
+            "context" => RepeatState::Context,
+            _ => RepeatState::Off,
+        }
+    }
+}
+
+/// A playback volume, guaranteed to be a percentage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume(u8);
+
+impl Volume {
+    pub const MAX: u8 = 100;
+
+    pub fn new(percent: u8) -> Result<Self> {
+        if percent > Self::MAX {
+            return Err(SptError::InvalidInput(format!(
+                "volume must be between 0 and {}, got {percent}",
+                Self::MAX
+            )));
+        }
+        Ok(Self(percent))
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.0
+    }
+}
+
+impl FromStr for Volume {
+    type Err = SptError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let percent = s
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| SptError::InvalidInput(format!("invalid volume \"{s}\"")))?;
+        Self::new(percent)
+    }
+}
+
+/// A device Spotify can play on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    /// `None` for devices the Web API cannot control.
+    pub id: Option<String>,
+    pub name: String,
+    pub kind: String,
+    pub volume: Option<i64>,
+    pub is_active: bool,
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let marker = if self.is_active { "*" } else { " " };
+        let volume = self
+            .volume
+            .map_or_else(|| "-".to_string(), |v| format!("{v}%"));
+        write!(f, "{marker} {} | {} | {volume}", self.name, self.kind)
+    }
+}
+
+/// Picks the device `query` refers to: its id, or its name matched exactly, as a prefix, as a
+/// substring, or as a subsequence (all ignoring case), taking the first of those that matches
+/// anything. A query that matches several devices equally well is an error.
+pub fn find_device<'a>(devices: &'a [Device], query: &str) -> Result<&'a Device> {
+    if let Some(device) = devices.iter().find(|d| d.id.as_deref() == Some(query)) {
+        return Ok(device);
+    }
+    let query = query.to_lowercase();
+    let is_subsequence = |name: &str| {
+        let mut name = name.chars();
+        query.chars().all(|c| name.any(|n| n == c))
+    };
+    let tiers: [&dyn Fn(&str) -> bool; 4] = [
+        &|name| name == query,
+        &|name| name.starts_with(&query),
+        &|name| name.contains(&query),
+        &is_subsequence,
+    ];
+    for matches in tiers {
+        let found: Vec<&Device> = devices
+            .iter()
+            .filter(|d| matches(&d.name.to_lowercase()))
+            .collect();
+        match found.as_slice() {
+            [] => continue,
+            [device] => return Ok(device),
+            several => {
+                let names: Vec<&str> = several.iter().map(|d| d.name.as_str()).collect();
+                return Err(SptError::InvalidInput(format!(
+                    "\"{query}\" matches several devices: {}",
+                    names.join(", ")
+                )));
+            }
+        }
+    }
+    Err(SptError::NotFound(format!("device \"{query}\"")))
+}
+
+/// The track the player is on; serializes as the track's fields plus the playback ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrentlyPlaying {
+    #[serde(flatten)]
+    pub song: Song,
+    /// `track` or `episode`.
+    pub kind: String,
+    pub progress_ms: i64,
+    pub is_playing: bool,
+}
+
+impl Display for CurrentlyPlaying {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.song.fmt(f)
+    }
+}
+
+/// A volume to set, or a step up or down from the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChange {
+    Set(Volume),
+    By(i16),
+}
+
+impl VolumeChange {
+    /// The volume this change leads to from `current`, kept within 0 to 100.
+    pub fn apply(&self, current: u8) -> Volume {
+        match *self {
+            VolumeChange::Set(volume) => volume,
+            VolumeChange::By(step) => {
+                Volume((current as i16 + step).clamp(0, Volume::MAX as i16) as u8)
+            }
+        }
+    }
+}
+
+impl FromStr for VolumeChange {
+    type Err = SptError;
 
-    let mut songs: Vec<Song> = vec![];
-    for song in res.tracks.items {
-        songs.push(Song {
-            name: song.track.name,
-            album: song.track.album.name,
-            artist: song.track.artists[0].name.clone(),
-            uri: "".to_string(),
+    /// `40` sets the volume, `+5` and `-10` step it.
+    fn from_str(s: &str) -> Result<Self> {
+        let input = s.trim();
+        if !input.starts_with(['+', '-']) {
+            return input.parse().map(VolumeChange::Set);
+        }
+        input
+            .parse::<i16>()
+            .ok()
+            .filter(|step| step.unsigned_abs() <= Volume::MAX as u16)
+            .map(VolumeChange::By)
+            .ok_or_else(|| SptError::InvalidInput(format!("invalid volume step \"{s}\"")))
+    }
+}
+
+/// The track in a playlist or album to start playing from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Offset {
+    /// The track's number, counting from 1.
+    Position(usize),
+    Uri(String),
+}
+
+impl FromStr for Offset {
+    type Err = SptError;
+
+    /// `3` for the third track, or a `spotify:track:` URI.
+    fn from_str(s: &str) -> Result<Self> {
+        let input = s.trim();
+        if input.starts_with("spotify:track:") {
+            return Ok(Offset::Uri(input.to_string()));
+        }
+        match input.parse::<usize>() {
+            Ok(position) if position > 0 => Ok(Offset::Position(position)),
+            _ => Err(SptError::InvalidInput(format!(
+                "expected a track number from 1 or a spotify:track: URI, got \"{s}\""
+            ))),
+        }
+    }
+}
+
+/// Where `spt seek` jumps to: a position in the track, or an offset from the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekTarget {
+    Absolute(i64),
+    Relative(i64),
+}
+
+impl SeekTarget {
+    /// The position to seek to in a track of `duration_ms` currently at `progress_ms`.
+    /// Seeking back past the start lands on it; seeking past the end is an error.
+    pub fn resolve(&self, progress_ms: i64, duration_ms: i64) -> Result<i64> {
+        let position = match *self {
+            SeekTarget::Absolute(ms) => ms,
+            SeekTarget::Relative(ms) => progress_ms
+                .checked_add(ms)
+                .ok_or_else(|| SptError::InvalidInput("position is out of range".to_string()))?
+                .max(0),
+        };
+        if position > duration_ms {
+            return Err(SptError::InvalidInput(format!(
+                "{} is past the end of the track ({})",
+                crate::template::format_duration(position),
+                crate::template::format_duration(duration_ms)
+            )));
+        }
+        Ok(position)
+    }
+}
+
+impl FromStr for SeekTarget {
+    type Err = SptError;
+
+    /// Accepts `1:23`, `1:02:03`, `83`, `83s`, `500ms` or `2m`, prefixed with `+` or `-` to
+    /// move relative to the current position.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            SptError::InvalidInput(format!(
+                "invalid position \"{s}\", expected e.g. 1:23, +30s or -10s"
+            ))
+        };
+        let input = s.trim();
+        let (sign, rest) = match input.as_bytes().first() {
+            Some(b'+') => (Some(1), &input[1..]),
+            Some(b'-') => (Some(-1), &input[1..]),
+            _ => (None, input),
+        };
+        let ms = if rest.contains(':') {
+            let mut secs: i64 = 0;
+            for (i, part) in rest.split(':').enumerate() {
+                let value = part.parse::<i64>().map_err(|_| invalid())?;
+                if i > 2 || (i > 0 && (part.len() != 2 || value >= 60)) {
+                    return Err(invalid());
+                }
+                secs = secs
+                    .checked_mul(60)
+                    .and_then(|secs| secs.checked_add(value))
+                    .ok_or_else(invalid)?;
+            }
+            secs.checked_mul(1000).ok_or_else(invalid)?
+        } else {
+            let (number, unit) = match rest.find(|c: char| !c.is_ascii_digit()) {
+                Some(i) => rest.split_at(i),
+                None => (rest, "s"),
+            };
+            let number = number.parse::<i64>().map_err(|_| invalid())?;
+            let scale = match unit {
+                "ms" => 1,
+                "s" => 1000,
+                "m" => 60_000,
+                _ => return Err(invalid()),
+            };
+            number.checked_mul(scale).ok_or_else(invalid)?
+        };
+        Ok(match sign {
+            Some(sign) => SeekTarget::Relative(sign * ms),
+            None => SeekTarget::Absolute(ms),
         })
     }
-    Ok(songs)
 }
 
-pub async fn get_currently_playing(
-    spotify_token: SpotifyAccessToken,
-) -> Result<Song, anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/currently-playing";
+/// What the player is playing from, e.g. a playlist or album.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackContext {
+    /// `playlist`, `album`, `artist` or `show`.
+    pub kind: String,
+    pub uri: String,
+    /// `None` when it could not be looked up, e.g. for Spotify's generated playlists.
+    pub name: Option<String>,
+}
 
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .bearer_auth(spotify_token.access_token)
-        .query(&[("market", "US")])
-        .send()
-        .await?
-        .json::<SpotifyCurrentlyPlayingRes>()
-        .await?;
+/// Everything `/me/player` reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackState {
+    pub is_playing: bool,
+    pub progress_ms: i64,
+    /// `track`, `episode`, `ad` or `unknown`.
+    pub kind: String,
+    /// `None` between tracks or while an ad plays.
+    pub item: Option<Song>,
+    pub device: Device,
+    pub shuffle: bool,
+    pub repeat: RepeatState,
+    pub context: Option<PlaybackContext>,
+}
 
-    let song: Song = Song {
-        name: res.item.name,
-        album: res.item.album.name,
-        artist: res.item.artists[0].name.clone(),
-        uri: res.item.uri,
+/// Width of the progress bar in [`PlaybackState`]'s text form.
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// `[=====>-----]` for `progress_ms` of `duration_ms`.
+pub fn progress_bar(progress_ms: i64, duration_ms: i64, width: usize) -> String {
+    let filled = match duration_ms {
+        d if d > 0 => (progress_ms.clamp(0, d) as usize * width) / d as usize,
+        _ => 0,
     };
+    let head = if filled < width { ">" } else { "" };
+    format!(
+        "[{}{head}{}]",
+        "=".repeat(filled),
+        "-".repeat(width.saturating_sub(filled + head.len()))
+    )
+}
 
-    Ok(song)
+impl Display for PlaybackState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.is_playing { "Playing" } else { "Paused" };
+        match &self.item {
+            Some(song) => {
+                writeln!(f, "{state}: {song}")?;
+                writeln!(
+                    f,
+                    "{} {} / {}",
+                    progress_bar(self.progress_ms, song.duration_ms, PROGRESS_BAR_WIDTH),
+                    crate::template::format_duration(self.progress_ms),
+                    crate::template::format_duration(song.duration_ms)
+                )?;
+            }
+            None => writeln!(f, "{state}: nothing")?,
+        }
+        let volume = self
+            .device
+            .volume
+            .map_or_else(|| "unknown".to_string(), |v| format!("{v}%"));
+        writeln!(
+            f,
+            "Device: {} ({}), volume {volume}",
+            self.device.name, self.device.kind
+        )?;
+        write!(
+            f,
+            "Shuffle: {}, repeat: {}",
+            if self.shuffle { "on" } else { "off" },
+            self.repeat.as_str()
+        )?;
+        if let Some(context) = &self.context {
+            let name = context.name.as_deref().unwrap_or(&context.uri);
+            write!(f, "\nPlaying from: {} {name}", context.kind)?;
+        }
+        Ok(())
+    }
 }
 
-pub async fn skip_to_next(spotify_token: SpotifyAccessToken) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/next";
+pub type TokenFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
 
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .send()
-        .await?;
+/// Supplies the bearer token for every request made by a [`SpotifyClient`].
+pub trait TokenProvider: Send + Sync {
+    fn access_token(&self) -> TokenFuture<'_>;
 
-    Ok(())
+    /// Called once when Spotify rejects the `stale` token with a 401. Implementations should
+    /// replace it unless another caller already has, and return the token to retry with.
+    fn refresh(&self, _stale: String) -> TokenFuture<'_> {
+        Box::pin(async { Err(SptError::AuthExpired) })
+    }
 }
 
-pub async fn skip_to_prev(spotify_token: SpotifyAccessToken) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/previous";
+impl TokenProvider for SpotifyAccessToken {
+    fn access_token(&self) -> TokenFuture<'_> {
+        Box::pin(async move { Ok(self.access_token.clone()) })
+    }
+}
+
+fn device_from(device: devices::Device) -> Device {
+    Device {
+        id: device.id,
+        name: device.name,
+        kind: device.type_field,
+        volume: device.volume_percent,
+        is_active: device.is_active,
+    }
+}
+
+/// An episode shows up as a song by its publisher, on the show's "album".
+fn playing_song(item: PlayingItem) -> Song {
+    match item {
+        PlayingItem::Track(track) => Song {
+            id: track.id.unwrap_or_default(),
+            uri: track.uri,
+            name: track.name,
+            artist: first_artist(track.artists.iter().map(|a| &a.name)),
+            album: track.album.name,
+            duration_ms: track.duration_ms,
+        },
+        PlayingItem::Episode(episode) => Song {
+            id: episode.id.unwrap_or_default(),
+            uri: episode.uri,
+            name: episode.name,
+            artist: episode.show.publisher,
+            album: episode.show.name,
+            duration_ms: episode.duration_ms,
+        },
+    }
+}
+
+fn first_artist<'a>(mut names: impl Iterator<Item = &'a String>) -> String {
+    names.next().cloned().unwrap_or_default()
+}
+
+/// A Spotify Web API client sharing one connection pool across all requests.
+#[derive(Clone)]
+pub struct SpotifyClient {
+    http: reqwest::Client,
+    token: Arc<dyn TokenProvider>,
+    base_url: String,
+    retry: RetryPolicy,
+    limiter: Arc<Semaphore>,
+    device_id: Option<String>,
+}
+
+impl SpotifyClient {
+    pub fn new(token: impl TokenProvider + 'static) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            token: Arc::new(token),
+            base_url: DEFAULT_API_URL.to_string(),
+            retry: RetryPolicy::default(),
+            limiter: Arc::new(Semaphore::new(DEFAULT_CONCURRENCY_LIMIT)),
+            device_id: None,
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Caps how many requests this client (and its clones) keep in flight at once.
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        self.limiter = Arc::new(Semaphore::new(limit.max(1)));
+        self
+    }
+
+    /// Sends playback commands to this device instead of the active one.
+    pub fn with_device(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The device picked with [`with_device`](Self::with_device), if any.
+    pub fn device(&self) -> Option<&str> {
+        self.device_id.as_deref()
+    }
+
+    /// Targets the device picked with [`with_device`](Self::with_device), if any.
+    fn on_device(&self, req: RequestBuilder) -> RequestBuilder {
+        match &self.device_id {
+            Some(id) => req.query(&[("device_id", id)]),
+            None => req,
+        }
+    }
+
+    async fn send<F>(&self, method: Method, path: &str, build: F) -> Result<Response>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        self.send_url(method, &format!("{}{}", self.base_url, path), build)
+            .await
+    }
+
+    /// Sends a request to `url`, refreshing the token once if it was rejected, waiting out
+    /// `Retry-After` on 429 and backing off on 5xx for idempotent methods, as allowed by the
+    /// [`RetryPolicy`].
+    async fn send_url<F>(&self, method: Method, url: &str, build: F) -> Result<Response>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let _permit = self
+            .limiter
+            .acquire()
+            .await
+            .expect("the limiter is never closed");
+        let mut token = self.token.access_token().await?;
+        let mut refreshed = false;
+        let mut attempt = 0;
+        loop {
+            let res = build(self.http.request(method.clone(), url).bearer_auth(&token))
+                .send()
+                .await?;
+            let status = res.status();
+            if status == StatusCode::UNAUTHORIZED && !refreshed {
+                token = self.token.refresh(token).await?;
+                refreshed = true;
+                continue;
+            }
+            if attempt < self.retry.max_retries {
+                let wait = if status == StatusCode::TOO_MANY_REQUESTS {
+                    Some(retry_after(res.headers()).unwrap_or_else(|| self.retry.backoff(attempt)))
+                        .filter(|wait| *wait <= self.retry.max_retry_after)
+                } else if status.is_server_error() && method.is_idempotent() {
+                    // A POST may have gone through despite the 5xx, and a retry would repeat it.
+                    Some(self.retry.backoff(attempt))
+                } else {
+                    None
+                };
+                if let Some(wait) = wait {
+                    attempt += 1;
+                    tokio::time::sleep(wait).await;
+                    continue;
+                }
+            }
+            return check(res).await;
+        }
+    }
+
+    /// Streams every item of a paged endpoint, following `next` links until the last page.
+    pub fn paginate<T>(
+        &self,
+        path: &str,
+        query: Vec<(&'static str, String)>,
+    ) -> impl Stream<Item = Result<T>> + '_
+    where
+        T: DeserializeOwned,
+    {
+        let cursor = Cursor::First(path.to_string(), query);
+        stream::try_unfold(cursor, move |cursor| async move {
+            let res = match cursor {
+                Cursor::First(path, query) => {
+                    self.send(Method::GET, &path, |req| req.query(&query))
+                        .await?
+                }
+                Cursor::Next(url) => self.send_url(Method::GET, &url, |req| req).await?,
+                Cursor::Done => return Ok::<_, SptError>(None),
+            };
+            let page = res.json::<Paging<T>>().await?;
+            let next = page.next.map_or(Cursor::Done, Cursor::Next);
+            Ok(Some((stream::iter(page.items.into_iter().map(Ok)), next)))
+        })
+        .try_flatten()
+    }
+
+    pub fn playlists(&self) -> impl Stream<Item = Result<Playlist>> + '_ {
+        self.paginate::<all_playlists::Item>("/me/playlists", vec![("limit", "50".to_string())])
+            .map_ok(|playlist| Playlist {
+                id: playlist.id,
+                uri: playlist.uri,
+                name: playlist.name,
+                owner: playlist.owner.display_name,
+                tracks: playlist.tracks.total,
+            })
+    }
+
+    pub fn playlist_tracks(&self, id: &str) -> impl Stream<Item = Result<Song>> + '_ {
+        self.paginate::<playlist::Item>(
+            &format!("/playlists/{}/tracks", id),
+            vec![
+                ("market", "US".to_string()),
+                ("limit", "100".to_string()),
+                (
+                    "fields",
+                    "next,items(track(id,name,uri,duration_ms,artists(name),album(name)))"
+                        .to_string(),
+                ),
+            ],
+        )
+        // Removed and unavailable tracks come back as `null` and are left out.
+        .try_filter_map(|item| async move {
+            Ok(item.track.map(|track| Song {
+                id: track.id.unwrap_or_default(),
+                uri: track.uri,
+                name: track.name,
+                artist: first_artist(track.artists.iter().map(|a| &a.name)),
+                album: track.album.name,
+                duration_ms: track.duration_ms,
+            }))
+        })
+    }
+
+    /// The user's Liked Songs, most recently added first, skipping the first `offset`.
+    pub fn liked(&self, offset: usize) -> impl Stream<Item = Result<Song>> + '_ {
+        self.paginate::<SavedTrack>(
+            "/me/tracks",
+            vec![
+                ("market", "US".to_string()),
+                ("limit", "50".to_string()),
+                ("offset", offset.to_string()),
+            ],
+        )
+        .map_ok(|saved| playing_song(PlayingItem::Track(Box::new(saved.track))))
+    }
+
+    pub async fn get_all_playlists(&self) -> Result<Vec<Playlist>> {
+        self.playlists().try_collect().await
+    }
+
+    pub async fn get_playlist(&self, id: &str) -> Result<Vec<Song>> {
+        self.playlist_tracks(id).try_collect().await
+    }
 
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .send()
+    /// The track or episode behind `uri`.
+    pub async fn get_item(&self, uri: &SpotifyUri) -> Result<Song> {
+        let path = match uri.kind {
+            UriKind::Track => format!("/tracks/{}", uri.id),
+            UriKind::Episode => format!("/episodes/{}", uri.id),
+            _ => {
+                return Err(SptError::InvalidInput(format!(
+                    "{uri} is not a track or episode"
+                )))
+            }
+        };
+        let item = self
+            .send(Method::GET, &path, |req| req.query(&[("market", "US")]))
+            .await?
+            .json::<PlayingItem>()
+            .await?;
+
+        Ok(playing_song(item))
+    }
+
+    pub async fn get_currently_playing(&self) -> Result<CurrentlyPlaying> {
+        let res = self
+            .send(Method::GET, "/me/player/currently-playing", |req| {
+                req.query(&[("market", "US"), ("additional_types", "track,episode")])
+            })
+            .await?;
+        if res.status() == StatusCode::NO_CONTENT {
+            return Err(SptError::NothingPlaying);
+        }
+        let res = res.json::<SpotifyCurrentlyPlayingRes>().await?;
+        // An ad, or something else Spotify will not describe.
+        let Some(item) = res.item else {
+            return Err(SptError::NothingPlaying);
+        };
+
+        Ok(CurrentlyPlaying {
+            kind: match item {
+                PlayingItem::Track(_) => "track",
+                PlayingItem::Episode(_) => "episode",
+            }
+            .to_string(),
+            song: playing_song(item),
+            progress_ms: res.progress_ms.unwrap_or_default(),
+            is_playing: res.is_playing,
+        })
+    }
+
+    pub async fn skip_to_next(&self) -> Result<()> {
+        self.send(Method::POST, "/me/player/next", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+        })
         .await?;
 
-    Ok(())
-}
-
-pub async fn search_for_item(
-    spotify_token: SpotifyAccessToken,
-    q: &str,
-) -> Result<Vec<Song>, anyhow::Error> {
-    let url = "https://api.spotify.com/v1/search";
-
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .bearer_auth(spotify_token.access_token)
-        .query(&[
-            ("q", q),
-            ("market", "US"),
-            ("type", "track"),
-            ("limit", "5"),
-        ])
-        .send()
-        .await?
-        .json::<SpotifySearchRes>()
+        Ok(())
+    }
+
+    pub async fn skip_to_prev(&self) -> Result<()> {
+        self.send(Method::POST, "/me/player/previous", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+        })
         .await?;
 
-    let mut songs: Vec<Song> = vec![];
-    for song in res.tracks.items {
-        songs.push(Song {
-            name: song.name,
-            album: song.album.name,
-            artist: song.artists[0].name.clone(),
-            uri: song.uri,
+        Ok(())
+    }
+
+    pub async fn search_for_item(&self, q: &str) -> Result<Vec<Song>> {
+        let res = self
+            .send(Method::GET, "/search", |req| {
+                req.query(&[
+                    ("q", q),
+                    ("market", "US"),
+                    ("type", "track"),
+                    ("limit", "5"),
+                ])
+            })
+            .await?
+            .json::<SpotifySearchRes>()
+            .await?;
+
+        let mut songs: Vec<Song> = vec![];
+        for song in res.tracks.items {
+            songs.push(Song {
+                id: song.id,
+                uri: song.uri,
+                name: song.name,
+                artist: first_artist(song.artists.iter().map(|a| &a.name)),
+                album: song.album.name,
+                duration_ms: song.duration_ms,
+            })
+        }
+
+        Ok(songs)
+    }
+
+    pub async fn add_to_queue(&self, uri: &str) -> Result<()> {
+        self.send(Method::POST, "/me/player/queue", |req| {
+            req.header(CONTENT_LENGTH, 0).query(&[("uri", uri)])
         })
+        .await?;
+
+        Ok(())
     }
 
-    Ok(songs)
-}
+    /// The current track and the upcoming ones, including those from the context.
+    pub async fn queue(&self) -> Result<Queue> {
+        let res = self
+            .send(Method::GET, "/me/player/queue", |req| req)
+            .await?
+            .json::<SpotifyQueueRes>()
+            .await?;
 
-pub async fn add_to_queue(
-    spotify_token: SpotifyAccessToken,
-    uri: String,
-) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/queue";
+        Ok(Queue {
+            currently_playing: res.currently_playing.map(playing_song),
+            queue: res
+                .queue
+                .into_iter()
+                .map(playing_song)
+                .enumerate()
+                .map(|(i, song)| QueuedSong {
+                    position: i + 1,
+                    song,
+                })
+                .collect(),
+        })
+    }
 
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .query(&[("uri", uri)])
-        .send()
+    /// Adds the track with `id` to Liked Songs.
+    pub async fn like(&self, id: &str) -> Result<()> {
+        self.send(Method::PUT, "/me/tracks", |req| {
+            req.header(CONTENT_LENGTH, 0).query(&[("ids", id)])
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
+
+    /// Removes the track with `id` from Liked Songs.
+    pub async fn unlike(&self, id: &str) -> Result<()> {
+        self.send(Method::DELETE, "/me/tracks", |req| {
+            req.header(CONTENT_LENGTH, 0).query(&[("ids", id)])
+        })
+        .await?;
+
+        Ok(())
+    }
 
-pub async fn pause(spotify_token: SpotifyAccessToken) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/pause";
+    /// Whether the track with `id` is in Liked Songs.
+    pub async fn is_liked(&self, id: &str) -> Result<bool> {
+        let res = self
+            .send(Method::GET, "/me/tracks/contains", |req| {
+                req.query(&[("ids", id)])
+            })
+            .await?
+            .json::<Vec<bool>>()
+            .await?;
 
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .send()
+        Ok(res.first().copied().unwrap_or_default())
+    }
+
+    pub async fn pause(&self) -> Result<()> {
+        self.send(Method::PUT, "/me/player/pause", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-pub async fn resume(spotify_token: SpotifyAccessToken) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/play";
+    pub async fn resume(&self) -> Result<()> {
+        self.send(Method::PUT, "/me/player/play", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+        })
+        .await?;
 
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .send()
+        Ok(())
+    }
+
+    /// Plays `uris` in order, starting `position_ms` into the first.
+    pub async fn start_playing(&self, uris: Vec<String>, position_ms: Option<i64>) -> Result<()> {
+        let json = SpotifyJSON { uris, position_ms };
+
+        self.send(Method::PUT, "/me/player/play", |req| {
+            self.on_device(req.header(CONTENT_TYPE, "application/json").json(&json))
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-pub async fn start_playing(
-    spotify_token: SpotifyAccessToken,
-    uris: Vec<String>,
-) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/play";
-    let json = SpotifyJSON { uris };
-
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_TYPE, "application/json")
-        .json(&json)
-        .send()
+    /// Plays a playlist, album or artist, from the `offset` track and `position_ms` into it.
+    pub async fn start_context(
+        &self,
+        context_uri: &str,
+        offset: Option<&Offset>,
+        position_ms: Option<i64>,
+    ) -> Result<()> {
+        let json = ContextJSON {
+            context_uri,
+            offset: offset.map(|offset| match offset {
+                Offset::Position(position) => OffsetJSON::Position(position - 1),
+                Offset::Uri(uri) => OffsetJSON::Uri(uri),
+            }),
+            position_ms,
+        };
+
+        self.send(Method::PUT, "/me/player/play", |req| {
+            self.on_device(req.header(CONTENT_TYPE, "application/json").json(&json))
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
+
+    /// The album that best matches `q`.
+    pub async fn find_album(&self, q: &str) -> Result<PlaybackContext> {
+        let res = self.search_by_type(q, "album").await?;
+        let album = res
+            .albums
+            .items
+            .into_iter()
+            .next()
+            .ok_or_else(|| SptError::NotFound(format!("album matching \"{q}\"")))?;
+        Ok(PlaybackContext {
+            kind: "album".to_string(),
+            uri: album.uri,
+            name: Some(album.name),
+        })
+    }
+
+    /// The artist that best matches `q`.
+    pub async fn find_artist(&self, q: &str) -> Result<PlaybackContext> {
+        let res = self.search_by_type(q, "artist").await?;
+        let artist = res
+            .artists
+            .items
+            .into_iter()
+            .next()
+            .ok_or_else(|| SptError::NotFound(format!("artist matching \"{q}\"")))?;
+        Ok(PlaybackContext {
+            kind: "artist".to_string(),
+            uri: artist.uri,
+            name: Some(artist.name),
+        })
+    }
 
-pub async fn shuffle(
-    spotify_token: SpotifyAccessToken,
-    shuffle_state: bool,
-) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/shuffle";
+    async fn search_by_type(&self, q: &str, kind: &str) -> Result<SpotifySearchRes> {
+        Ok(self
+            .send(Method::GET, "/search", |req| {
+                req.query(&[("q", q), ("market", "US"), ("type", kind), ("limit", "1")])
+            })
+            .await?
+            .json::<SpotifySearchRes>()
+            .await?)
+    }
 
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .query(&[("state", shuffle_state)])
-        .send()
+    pub async fn shuffle(&self, shuffle_state: bool) -> Result<()> {
+        self.send(Method::PUT, "/me/player/shuffle", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+                .query(&[("state", shuffle_state)])
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-pub async fn repeat(
-    spotify_token: SpotifyAccessToken,
-    repeat_state: String,
-) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/repeat";
+    pub async fn repeat(&self, repeat_state: RepeatState) -> Result<()> {
+        self.send(Method::PUT, "/me/player/repeat", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+                .query(&[("state", repeat_state.as_str())])
+        })
+        .await?;
 
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .query(&[("state", repeat_state)])
-        .send()
+        Ok(())
+    }
+
+    /// Appends `uris` to a playlist, split into as many requests as Spotify requires.
+    pub async fn add_to_playlist(&self, pid: &str, uris: Vec<String>) -> Result<()> {
+        for chunk in uris.chunks(MAX_URIS_PER_REQUEST) {
+            let json = SpotifyJSON {
+                uris: chunk.to_vec(),
+                position_ms: None,
+            };
+
+            self.send(Method::POST, &format!("/playlists/{}/tracks", pid), |req| {
+                req.header(CONTENT_TYPE, "application/json").json(&json)
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn add_current_to_playlist(&self) -> Result<()> {
+        let all_pls = self.get_all_playlists().await?;
+        let songs_pl = all_pls
+            .iter()
+            .find(|p| p.name == "songs")
+            .ok_or_else(|| SptError::NotFound("playlist \"songs\"".to_string()))?;
+        let curr_song = self.get_currently_playing().await?;
+        self.add_to_playlist(&songs_pl.id, vec![curr_song.song.uri])
+            .await
+    }
+
+    pub async fn volume(&self, volume: Volume) -> Result<()> {
+        self.send(Method::PUT, "/me/player/volume", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+                .query(&[("volume_percent", volume.percent())])
+        })
         .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-pub async fn add_to_playlist(
-    spotify_token: SpotifyAccessToken,
-    pid: String,
-    uris: Vec<String>,
-) -> Result<(), anyhow::Error> {
-    let url = format!("https://api.spotify.com/v1/playlists/{}/tracks", pid);
-    let json = SpotifyJSON { uris };
-
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_TYPE, "application/json")
-        .json(&json)
-        .send()
-        .await?
-        .text()
+    /// The full playback state, with the context's name looked up.
+    pub async fn playback_state(&self) -> Result<PlaybackState> {
+        let res = self
+            .send(Method::GET, "/me/player", |req| {
+                req.query(&[("market", "US"), ("additional_types", "track,episode")])
+            })
+            .await?;
+        if res.status() == StatusCode::NO_CONTENT {
+            return Err(SptError::NothingPlaying);
+        }
+        let res = res.json::<SpotifyPlayerRes>().await?;
+
+        let context = match res.context {
+            Some(context) => Some(PlaybackContext {
+                name: self.context_name(&context.uri).await,
+                kind: context.type_field,
+                uri: context.uri,
+            }),
+            None => None,
+        };
+        Ok(PlaybackState {
+            is_playing: res.is_playing,
+            progress_ms: res.progress_ms.unwrap_or_default(),
+            kind: res.currently_playing_type,
+            item: res.item.map(playing_song),
+            device: device_from(res.device),
+            shuffle: res.shuffle_state,
+            repeat: RepeatState::from_api(&res.repeat_state),
+            context,
+        })
+    }
+
+    /// The name of the playlist, album or artist behind a context URI, if Spotify has one.
+    async fn context_name(&self, uri: &str) -> Option<String> {
+        let mut parts = uri.split(':');
+        let (path, query) = match (parts.next(), parts.next(), parts.next()) {
+            (Some("spotify"), Some("playlist"), Some(id)) => {
+                (format!("/playlists/{id}"), vec![("fields", "name")])
+            }
+            (Some("spotify"), Some("album"), Some(id)) => (format!("/albums/{id}"), vec![]),
+            (Some("spotify"), Some("artist"), Some(id)) => (format!("/artists/{id}"), vec![]),
+            _ => return None,
+        };
+        let res = self
+            .send(Method::GET, &path, |req| req.query(&query))
+            .await
+            .ok()?;
+        res.json::<Named>().await.ok().map(|named| named.name)
+    }
+
+    pub async fn seek(&self, position_ms: i64) -> Result<()> {
+        self.send(Method::PUT, "/me/player/seek", |req| {
+            self.on_device(req.header(CONTENT_LENGTH, 0))
+                .query(&[("position_ms", position_ms)])
+        })
         .await?;
 
-    Ok(())
-}
-
-pub async fn add_current_to_playlist(
-    spotify_token: SpotifyAccessToken,
-) -> Result<(), anyhow::Error> {
-    let all_pls = get_all_playlists(spotify_token.clone()).await?;
-    let songs_pl = all_pls
-        .iter()
-        .find(|p| p.name == "songs")
-        .expect("songs playlist to exist");
-    let url = format!(
-        "https://api.spotify.com/v1/playlists/{}/tracks",
-        songs_pl.id
-    );
-    let curr_song = get_currently_playing(spotify_token.clone()).await?;
-    let uris = vec![curr_song.uri];
-    let json = SpotifyJSON { uris };
-
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_TYPE, "application/json")
-        .json(&json)
-        .send()
-        .await?
-        .text()
+        Ok(())
+    }
+
+    pub async fn devices(&self) -> Result<Vec<Device>> {
+        let res = self
+            .send(Method::GET, "/me/player/devices", |req| req)
+            .await?
+            .json::<SpotifyDevicesRes>()
+            .await?;
+
+        Ok(res.devices.into_iter().map(device_from).collect())
+    }
+
+    /// Moves playback to `device_id`, starting it if `play` is set.
+    pub async fn transfer(&self, device_id: &str, play: bool) -> Result<()> {
+        let json = TransferJSON {
+            device_ids: [device_id],
+            play,
+        };
+
+        self.send(Method::PUT, "/me/player", |req| {
+            req.header(CONTENT_TYPE, "application/json").json(&json)
+        })
         .await?;
 
-    Ok(())
+        Ok(())
+    }
 }
 
-pub async fn volume(
-    spotify_token: SpotifyAccessToken,
-    percentage: usize,
-) -> Result<(), anyhow::Error> {
-    let url = "https://api.spotify.com/v1/me/player/volume";
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let client = reqwest::Client::new();
-    client
-        .put(url)
-        .bearer_auth(spotify_token.access_token)
-        .header(CONTENT_LENGTH, 0)
-        .query(&[("volume_percent", percentage)])
-        .send()
-        .await?;
+    #[test]
+    fn parses_seek_targets() {
+        let parse = |s: &str| s.parse::<SeekTarget>().unwrap();
+        assert_eq!(parse("1:23"), SeekTarget::Absolute(83_000));
+        assert_eq!(parse("1:02:03"), SeekTarget::Absolute(3_723_000));
+        assert_eq!(parse("0:05"), SeekTarget::Absolute(5_000));
+        assert_eq!(parse("83"), SeekTarget::Absolute(83_000));
+        assert_eq!(parse("83s"), SeekTarget::Absolute(83_000));
+        assert_eq!(parse("500ms"), SeekTarget::Absolute(500));
+        assert_eq!(parse("2m"), SeekTarget::Absolute(120_000));
+        assert_eq!(parse("+30s"), SeekTarget::Relative(30_000));
+        assert_eq!(parse("-10"), SeekTarget::Relative(-10_000));
+        assert_eq!(parse("-1:00"), SeekTarget::Relative(-60_000));
+        assert_eq!(parse(" +2m "), SeekTarget::Relative(120_000));
+    }
+
+    #[test]
+    fn rejects_invalid_seek_targets() {
+        for input in [
+            "",
+            "+",
+            "abc",
+            "1:2",
+            "1:60",
+            "1:00:00:00",
+            "1::00",
+            "10h",
+            "1.5s",
+            "--5",
+            "+999999999999999m",
+            "9223372036854775807s",
+            "99999999999999999:00:00",
+        ] {
+            assert!(
+                matches!(input.parse::<SeekTarget>(), Err(SptError::InvalidInput(_))),
+                "{input}"
+            );
+        }
+    }
 
-    Ok(())
+    #[test]
+    fn resolves_seek_targets() {
+        assert_eq!(
+            SeekTarget::Absolute(1_000).resolve(5_000, 10_000).unwrap(),
+            1_000
+        );
+        assert_eq!(
+            SeekTarget::Relative(2_000).resolve(5_000, 10_000).unwrap(),
+            7_000
+        );
+        assert_eq!(
+            SeekTarget::Relative(-9_000).resolve(5_000, 10_000).unwrap(),
+            0
+        );
+        assert!(SeekTarget::Relative(6_000).resolve(5_000, 10_000).is_err());
+        assert!(SeekTarget::Absolute(10_001).resolve(0, 10_000).is_err());
+        assert!(SeekTarget::Relative(i64::MAX)
+            .resolve(5_000, 10_000)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_volume_changes() {
+        let parse = |s: &str| s.parse::<VolumeChange>().unwrap();
+        assert_eq!(parse("40"), VolumeChange::Set(Volume(40)));
+        assert_eq!(parse("0"), VolumeChange::Set(Volume(0)));
+        assert_eq!(parse("100"), VolumeChange::Set(Volume(100)));
+        assert_eq!(parse("+5"), VolumeChange::By(5));
+        assert_eq!(parse("-10"), VolumeChange::By(-10));
+        assert_eq!(parse(" -100 "), VolumeChange::By(-100));
+    }
+
+    #[test]
+    fn rejects_invalid_volume_changes() {
+        for input in ["", "101", "-101", "+", "loud", "5%", "+-5"] {
+            assert!(
+                matches!(
+                    input.parse::<VolumeChange>(),
+                    Err(SptError::InvalidInput(_))
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn clamps_volume_steps() {
+        assert_eq!(VolumeChange::By(10).apply(50), Volume(60));
+        assert_eq!(VolumeChange::By(10).apply(95), Volume(100));
+        assert_eq!(VolumeChange::By(-10).apply(5), Volume(0));
+        assert_eq!(VolumeChange::Set(Volume(30)).apply(80), Volume(30));
+    }
+
+    fn device(id: Option<&str>, name: &str) -> Device {
+        Device {
+            id: id.map(str::to_string),
+            name: name.to_string(),
+            kind: "Speaker".to_string(),
+            volume: None,
+            is_active: false,
+        }
+    }
+
+    fn find<'a>(devices: &'a [Device], query: &str) -> Result<&'a str> {
+        find_device(devices, query).map(|d| d.name.as_str())
+    }
+
+    #[test]
+    fn ranks_device_matches() {
+        let devices = [
+            device(Some("a1"), "Kitchen Speaker"),
+            device(Some("b2"), "Kitchen"),
+            device(Some("c3"), "Living Room TV"),
+            device(Some("d4"), "My Laptop"),
+        ];
+        // An id wins, then an exact name, a prefix, a substring and a subsequence.
+        assert_eq!(find(&devices, "c3").unwrap(), "Living Room TV");
+        assert_eq!(find(&devices, "KITCHEN").unwrap(), "Kitchen");
+        assert_eq!(find(&devices, "liv").unwrap(), "Living Room TV");
+        assert_eq!(find(&devices, "laptop").unwrap(), "My Laptop");
+        assert_eq!(find(&devices, "lrtv").unwrap(), "Living Room TV");
+    }
+
+    #[test]
+    fn reports_ambiguous_and_unknown_devices() {
+        let devices = [
+            device(Some("a1"), "Kitchen Speaker"),
+            device(Some("b2"), "Bedroom Speaker"),
+            device(None, "Web Player"),
+        ];
+        let Err(SptError::InvalidInput(message)) = find(&devices, "speaker") else {
+            panic!("an ambiguous query matched one device");
+        };
+        assert!(message.contains("Kitchen Speaker") && message.contains("Bedroom Speaker"));
+        assert!(matches!(
+            find(&devices, "garage"),
+            Err(SptError::NotFound(_))
+        ));
+        // Devices without an id can still be found, to say they can't be controlled.
+        assert_eq!(find(&devices, "web").unwrap(), "Web Player");
+    }
 }
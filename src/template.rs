@@ -0,0 +1,342 @@
+//! Output templates for `--format` and the `[format]` config table.
+//!
+//! `{field}` is replaced by a field of the item being printed. `{?field}...{/}` keeps its
+//! body only if the field is set (non-empty, not `false` or `0`), `{!field}...{/}` only if it
+//! is not, and either may have an `{:}` branch for the other case. `{{` and `}}` print braces.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::{Result, SptError};
+use crate::spotify::{CurrentlyPlaying, PlaybackState, Playlist, Song};
+
+/// Every placeholder a template may use.
+pub const PLACEHOLDERS: &[&str] = &[
+    "name",
+    "artist",
+    "album",
+    "uri",
+    "id",
+    "duration",
+    "duration_ms",
+    "progress",
+    "progress_ms",
+    "is_playing",
+    "owner",
+    "tracks",
+    "device",
+    "volume",
+    "shuffle",
+    "repeat",
+    "context",
+];
+
+/// Something a template can be rendered against. Placeholders that do not apply to it render
+/// empty, so one template can be shared between kinds of items.
+pub trait Fields {
+    fn field(&self, name: &str) -> Option<String>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Field(String),
+    Cond {
+        field: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// What stopped [`parse_nodes`].
+#[derive(Debug, PartialEq, Eq)]
+enum End {
+    Eof,
+    Else,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(src: &str) -> Result<Self> {
+        let invalid = |message: String| {
+            SptError::InvalidInput(format!("invalid template \"{src}\": {message}"))
+        };
+        match parse_nodes(&mut src.chars().peekable()).map_err(invalid)? {
+            (nodes, End::Eof) => Ok(Self { nodes }),
+            _ => Err(invalid(
+                "{:} or {/} without an opening {?field}".to_string(),
+            )),
+        }
+    }
+
+    pub fn render(&self, item: &dyn Fields) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, item, &mut out);
+        out
+    }
+}
+
+/// Parses up to the end of input or the next `{:}` or `{/}`, whichever comes first.
+fn parse_nodes(chars: &mut Peekable<Chars>) -> std::result::Result<(Vec<Node>, End), String> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            None => {
+                flush(&mut text, &mut nodes);
+                return Ok((nodes, End::Eof));
+            }
+            Some('{') if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            Some('}') if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            Some('}') => return Err("unmatched }, write }} for a brace".to_string()),
+            Some('{') => {
+                let mut tag = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(format!("unclosed {{{tag}")),
+                    }
+                }
+                flush(&mut text, &mut nodes);
+                match tag.as_str() {
+                    ":" => return Ok((nodes, End::Else)),
+                    "/" => return Ok((nodes, End::Close)),
+                    _ => (),
+                }
+                let (negate, field) = match tag.strip_prefix('?') {
+                    Some(field) => (false, Some(field)),
+                    None => (true, tag.strip_prefix('!')),
+                };
+                let Some(field) = field else {
+                    nodes.push(Node::Field(known(&tag)?));
+                    continue;
+                };
+                let field = known(field)?;
+                let (then, end) = parse_nodes(chars)?;
+                let otherwise = match end {
+                    End::Close => vec![],
+                    End::Else => match parse_nodes(chars)? {
+                        (otherwise, End::Close) => otherwise,
+                        _ => return Err(format!("{{{tag}}} has no {{/}}")),
+                    },
+                    End::Eof => return Err(format!("{{{tag}}} has no {{/}}")),
+                };
+                nodes.push(Node::Cond {
+                    field,
+                    negate,
+                    then,
+                    otherwise,
+                });
+            }
+            Some(c) => text.push(c),
+        }
+    }
+}
+
+fn flush(text: &mut String, nodes: &mut Vec<Node>) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+fn known(field: &str) -> std::result::Result<String, String> {
+    if PLACEHOLDERS.contains(&field) {
+        Ok(field.to_string())
+    } else {
+        Err(format!(
+            "unknown placeholder {{{field}}}, expected one of {}",
+            PLACEHOLDERS.join(", ")
+        ))
+    }
+}
+
+fn render_nodes(nodes: &[Node], item: &dyn Fields, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field(field) => out.push_str(&item.field(field).unwrap_or_default()),
+            Node::Cond {
+                field,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let set = item
+                    .field(field)
+                    .is_some_and(|value| !matches!(value.as_str(), "" | "false" | "0"));
+                if set != *negate {
+                    render_nodes(then, item, out);
+                } else {
+                    render_nodes(otherwise, item, out);
+                }
+            }
+        }
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` for an hour or more.
+pub fn format_duration(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+impl Fields for Song {
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "name" => Some(self.name.clone()),
+            "artist" => Some(self.artist.clone()),
+            "album" => Some(self.album.clone()),
+            "uri" => Some(self.uri.clone()),
+            "id" => Some(self.id.clone()),
+            "duration" => Some(format_duration(self.duration_ms)),
+            "duration_ms" => Some(self.duration_ms.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Fields for CurrentlyPlaying {
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "progress" => Some(format_duration(self.progress_ms)),
+            "progress_ms" => Some(self.progress_ms.to_string()),
+            "is_playing" => Some(self.is_playing.to_string()),
+            _ => self.song.field(name),
+        }
+    }
+}
+
+impl Fields for Playlist {
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "name" => Some(self.name.clone()),
+            "owner" => Some(self.owner.clone()),
+            "uri" => Some(self.uri.clone()),
+            "id" => Some(self.id.clone()),
+            "tracks" => Some(self.tracks.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Fields for PlaybackState {
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "progress" => Some(format_duration(self.progress_ms)),
+            "progress_ms" => Some(self.progress_ms.to_string()),
+            "is_playing" => Some(self.is_playing.to_string()),
+            "device" => Some(self.device.name.clone()),
+            "volume" => self.device.volume.map(|v| v.to_string()),
+            "shuffle" => Some(self.shuffle.to_string()),
+            "repeat" => Some(self.repeat.as_str().to_string()),
+            "context" => self
+                .context
+                .as_ref()
+                .map(|c| c.name.clone().unwrap_or_else(|| c.uri.clone())),
+            _ => self.item.as_ref().and_then(|song| song.field(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Map(&'static [(&'static str, &'static str)]);
+
+    impl Fields for Map {
+        fn field(&self, name: &str) -> Option<String> {
+            self.0
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    fn render(src: &str, fields: &'static [(&'static str, &'static str)]) -> String {
+        Template::parse(src).unwrap().render(&Map(fields))
+    }
+
+    #[test]
+    fn renders_fields() {
+        let fields = &[("name", "Airbag"), ("artist", "Radiohead")];
+        assert_eq!(render("{artist} - {name}", fields), "Radiohead - Airbag");
+        assert_eq!(render("plain text", fields), "plain text");
+        assert_eq!(render("", fields), "");
+        // Placeholders that don't apply print nothing.
+        assert_eq!(render("{name} by {owner}", fields), "Airbag by ");
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let template = "{?is_playing}playing{:}paused{/}";
+        assert_eq!(render(template, &[("is_playing", "true")]), "playing");
+        assert_eq!(render(template, &[("is_playing", "false")]), "paused");
+        assert_eq!(render(template, &[]), "paused");
+        assert_eq!(render("{!tracks}empty{/}", &[("tracks", "0")]), "empty");
+        assert_eq!(render("{!tracks}empty{/}", &[("tracks", "12")]), "");
+        assert_eq!(
+            render("{?album}{name} ({album}){:}{name}{/}", &[("name", "Song")]),
+            "Song"
+        );
+        assert_eq!(
+            render(
+                "{?album}from {album}{?duration}, {duration}{/}{/}",
+                &[("album", "OK Computer"), ("duration", "4:44")]
+            ),
+            "from OK Computer, 4:44"
+        );
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{{name}}}", &[("name", "x")]), "{x}");
+        assert_eq!(render("}}{{", &[]), "}{");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for src in [
+            "{nonsense}",
+            "{?nonsense}x{/}",
+            "{name",
+            "name}",
+            "{?name}unclosed",
+            "{?name}a{:}b",
+            "{:}",
+            "{/}",
+            "{?name}a{:}b{:}c{/}",
+        ] {
+            assert!(
+                matches!(Template::parse(src), Err(SptError::InvalidInput(_))),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(83_999), "1:23");
+        assert_eq!(format_duration(3_723_000), "1:02:03");
+        assert_eq!(format_duration(-5), "0:00");
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use playlist::auth::refresh_token;
+use playlist::config::Config;
+use playlist::error::{Result, SptError};
+use playlist::spotify::{TokenFuture, TokenProvider};
+use tokio::sync::Mutex;
+
+use crate::db::{self, ClientCredentials, DBToken, Database};
+use crate::vault::Vault;
+
+/// Refresh this long before Spotify would reject the token.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Hands out the stored access token, refreshing and persisting it when it expires.
+///
+/// The token sits behind a mutex so concurrent requests that hit an expired token trigger
+/// a single refresh. The database is only reached when the token is loaded or refreshed.
+pub struct TokenManager {
+    db: Database,
+    vault: Vault,
+    profile: String,
+    config: Config,
+    creds: ClientCredentials,
+    token: Mutex<DBToken>,
+}
+
+impl TokenManager {
+    pub async fn new(
+        db: Database,
+        vault: Vault,
+        profile: String,
+        config: Config,
+        creds: ClientCredentials,
+    ) -> Result<Self> {
+        let token = db::select_db_token(&db.get().await?, &vault, &profile)
+            .await?
+            .ok_or(SptError::AuthExpired)?;
+        Ok(Self {
+            db,
+            vault,
+            profile,
+            config,
+            creds,
+            token: Mutex::new(token),
+        })
+    }
+
+    /// The scopes the stored token was granted.
+    pub async fn scope(&self) -> String {
+        self.token.lock().await.scope.clone()
+    }
+
+    async fn refresh_locked(&self, token: &mut DBToken) -> Result<()> {
+        let refreshed = refresh_token(
+            &self.config,
+            token.refresh_token.clone(),
+            &self.creds.flow(),
+        )
+        .await?;
+        let db = self.db.get().await?;
+        *token = db::update_token(&db, &self.vault, &self.profile, refreshed).await?;
+        Ok(())
+    }
+}
+
+impl TokenProvider for TokenManager {
+    fn access_token(&self) -> TokenFuture<'_> {
+        Box::pin(async move {
+            let mut token = self.token.lock().await;
+            if token.expires_within(REFRESH_MARGIN) {
+                self.refresh_locked(&mut token).await?;
+            }
+            Ok(token.access_token.clone())
+        })
+    }
+
+    fn refresh(&self, stale: String) -> TokenFuture<'_> {
+        Box::pin(async move {
+            let mut token = self.token.lock().await;
+            if token.access_token == stale {
+                self.refresh_locked(&mut token).await?;
+            }
+            Ok(token.access_token.clone())
+        })
+    }
+}
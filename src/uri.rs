@@ -0,0 +1,226 @@
+//! Spotify URIs and the open.spotify.com links that share them.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::error::{Result, SptError};
+
+/// What a [`SpotifyUri`] points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UriKind {
+    Track,
+    Episode,
+    Album,
+    Artist,
+    Playlist,
+    Show,
+}
+
+impl UriKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UriKind::Track => "track",
+            UriKind::Episode => "episode",
+            UriKind::Album => "album",
+            UriKind::Artist => "artist",
+            UriKind::Playlist => "playlist",
+            UriKind::Show => "show",
+        }
+    }
+
+    fn from_segment(segment: &str) -> Option<Self> {
+        match segment {
+            "track" => Some(UriKind::Track),
+            "episode" => Some(UriKind::Episode),
+            "album" => Some(UriKind::Album),
+            "artist" => Some(UriKind::Artist),
+            "playlist" => Some(UriKind::Playlist),
+            "show" => Some(UriKind::Show),
+            _ => None,
+        }
+    }
+}
+
+/// A track, episode, album, artist, playlist or show, parsed from a `spotify:` URI or a
+/// share link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotifyUri {
+    pub kind: UriKind,
+    pub id: String,
+}
+
+impl SpotifyUri {
+    fn new(kind: &str, id: &str) -> Option<Self> {
+        let valid_id = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+        Some(Self {
+            kind: UriKind::from_segment(kind)?,
+            id: valid_id.then(|| id.to_string())?,
+        })
+    }
+
+    /// `spotify:{kind}:{id}`.
+    pub fn uri(&self) -> String {
+        format!("spotify:{}:{}", self.kind.as_str(), self.id)
+    }
+
+    /// Whether it is a single track or episode, rather than something that holds them.
+    pub fn is_item(&self) -> bool {
+        matches!(self.kind, UriKind::Track | UriKind::Episode)
+    }
+
+    /// Whether `input` is meant as a URI or link, even if it does not parse as one, so that it
+    /// is reported as invalid instead of being searched for.
+    pub fn looks_like(input: &str) -> bool {
+        let input = input.trim();
+        input.starts_with("spotify:")
+            || input.contains("open.spotify.com/")
+            || input.contains("spotify.link/")
+    }
+}
+
+impl Display for SpotifyUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.uri())
+    }
+}
+
+impl FromStr for SpotifyUri {
+    type Err = SptError;
+
+    /// Accepts `spotify:track:ID` and the like, and `https://open.spotify.com/track/ID?si=...`
+    /// links, including localized (`/intl-de/`), embed and old `/user/NAME/playlist/` ones.
+    fn from_str(s: &str) -> Result<Self> {
+        let input = s.trim();
+        let invalid = || SptError::InvalidInput(format!("not a Spotify URI or link: \"{input}\""));
+
+        if let Some(rest) = input.strip_prefix("spotify:") {
+            let parts: Vec<&str> = rest.split(':').collect();
+            return match parts.as_slice() {
+                [kind, id] | ["user", _, kind, id] => Self::new(kind, id).ok_or_else(invalid),
+                _ => Err(invalid()),
+            };
+        }
+
+        let url = input
+            .strip_prefix("https://")
+            .or_else(|| input.strip_prefix("http://"))
+            .unwrap_or(input);
+        if url.starts_with("spotify.link/") {
+            return Err(SptError::InvalidInput(format!(
+                "{input} is a short link, open it in a browser and use the link it leads to"
+            )));
+        }
+        let path = url
+            .strip_prefix("open.spotify.com/")
+            .ok_or_else(invalid)?
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default();
+        let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+        let mut kind = segments.next();
+        if kind.is_some_and(|k| k.starts_with("intl-") || k == "embed") {
+            kind = segments.next();
+        }
+        if kind == Some("user") {
+            segments.next();
+            kind = segments.next();
+        }
+        match (kind, segments.next()) {
+            (Some(kind), Some(id)) => Self::new(kind, id).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> SpotifyUri {
+        input.parse().unwrap_or_else(|e| panic!("{input}: {e}"))
+    }
+
+    #[test]
+    fn parses_uris() {
+        let track = parse("spotify:track:4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(track.kind, UriKind::Track);
+        assert_eq!(track.id, "4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(track.uri(), "spotify:track:4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(parse(" spotify:episode:abc123 ").kind, UriKind::Episode);
+        assert_eq!(parse("spotify:show:abc123").kind, UriKind::Show);
+        let old = parse("spotify:user:someone:playlist:37i9dQZF1DXcBWIGoYBM5M");
+        assert_eq!(old.kind, UriKind::Playlist);
+        assert_eq!(old.id, "37i9dQZF1DXcBWIGoYBM5M");
+    }
+
+    #[test]
+    fn parses_links() {
+        let expected = SpotifyUri {
+            kind: UriKind::Album,
+            id: "1DFixLWuPkv3KT3TnV35m3".to_string(),
+        };
+        for link in [
+            "https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3",
+            "http://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3",
+            "open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3",
+            "https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3?si=a1b2c3",
+            "https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3#details",
+            "https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3/",
+            "https://open.spotify.com/intl-de/album/1DFixLWuPkv3KT3TnV35m3",
+            "https://open.spotify.com/embed/album/1DFixLWuPkv3KT3TnV35m3?utm_source=x",
+        ] {
+            assert_eq!(parse(link), expected, "{link}");
+        }
+        let old = parse("https://open.spotify.com/user/someone/playlist/37i9dQZF1DXcBWIGoYBM5M");
+        assert_eq!(old.kind, UriKind::Playlist);
+        assert_eq!(old.id, "37i9dQZF1DXcBWIGoYBM5M");
+    }
+
+    #[test]
+    fn rejects_other_input() {
+        for input in [
+            "",
+            "karma police",
+            "spotify:",
+            "spotify:track",
+            "spotify:track:",
+            "spotify:song:abc123",
+            "spotify:track:abc-123",
+            "spotify:track:abc:extra",
+            "https://open.spotify.com/",
+            "https://open.spotify.com/track",
+            "https://open.spotify.com/genre/abc123",
+            "https://example.com/track/abc123",
+        ] {
+            assert!(
+                matches!(input.parse::<SpotifyUri>(), Err(SptError::InvalidInput(_))),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn explains_short_links() {
+        let Err(SptError::InvalidInput(message)) = "https://spotify.link/abc".parse::<SpotifyUri>()
+        else {
+            panic!("a short link parsed");
+        };
+        assert!(message.contains("short link"), "{message}");
+    }
+
+    #[test]
+    fn tells_links_from_queries() {
+        assert!(SpotifyUri::looks_like("spotify:nonsense"));
+        assert!(SpotifyUri::looks_like("https://open.spotify.com/whatever"));
+        assert!(SpotifyUri::looks_like("spotify.link/abc"));
+        assert!(!SpotifyUri::looks_like("spotify sessions"));
+    }
+
+    #[test]
+    fn tells_items_from_containers() {
+        assert!(parse("spotify:track:abc").is_item());
+        assert!(parse("spotify:episode:abc").is_item());
+        assert!(!parse("spotify:album:abc").is_item());
+        assert!(!parse("spotify:artist:abc").is_item());
+    }
+}
@@ -0,0 +1,245 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use surrealdb::{engine::local::Db, Surreal};
+use tokio::sync::Mutex;
+
+use playlist::config::{SecretBackend, StorageConfig};
+use playlist::error::{Result, SptError};
+
+/// Marks a value sealed with AES-256-GCM: base64 of the 12-byte nonce followed by the ciphertext.
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+/// Stored in place of a value that lives in the keyring.
+const KEYRING_MARKER: &str = "keyring:";
+const KEYRING_SERVICE: &str = "spt";
+/// Encrypted alongside the salt so a wrong passphrase is caught before anything is decrypted.
+const CHECK_PLAINTEXT: &str = "spt";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptionSettings {
+    salt: String,
+    check: String,
+}
+
+/// Seals secret fields before they are written to the database and opens them after they are
+/// read, according to the configured [`SecretBackend`].
+///
+/// Whatever the backend, values are opened by their prefix, so switching backends keeps old
+/// records readable; they are sealed the new way the next time they are written.
+///
+/// The vault keeps no database handle of its own, so a derived key outlives the connection it
+/// was derived with.
+#[derive(Clone)]
+pub struct Vault {
+    backend: SecretBackend,
+    key_file: Option<PathBuf>,
+    cipher: Arc<Mutex<Option<Aes256Gcm>>>,
+}
+
+impl Vault {
+    pub fn new(storage: &StorageConfig) -> Self {
+        Self {
+            backend: storage.secrets,
+            key_file: storage.key_file.clone(),
+            cipher: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The form of `value` to store for `field` of `profile`.
+    pub async fn seal(
+        &self,
+        db: &Surreal<Db>,
+        profile: &str,
+        field: &str,
+        value: String,
+    ) -> Result<String> {
+        match self.backend {
+            SecretBackend::Plain => Ok(value),
+            SecretBackend::Encrypted => self.encrypt(db, &value).await,
+            SecretBackend::Keyring => {
+                match keyring_entry(profile, field).and_then(|entry| {
+                    entry
+                        .set_password(&value)
+                        .map_err(|e| SptError::Storage(format!("keyring: {e}")))
+                }) {
+                    Ok(()) => Ok(KEYRING_MARKER.to_string()),
+                    // No Secret Service on this machine (or it refused): keep it in the db.
+                    Err(_) => self.encrypt(db, &value).await,
+                }
+            }
+        }
+    }
+
+    /// The secret behind a value [`seal`](Self::seal) produced, or a plain value as is.
+    pub async fn open(
+        &self,
+        db: &Surreal<Db>,
+        profile: &str,
+        field: &str,
+        stored: String,
+    ) -> Result<String> {
+        if let Some(sealed) = stored.strip_prefix(ENCRYPTED_PREFIX) {
+            let cipher = self.cipher(db).await?;
+            return decrypt(&cipher, sealed);
+        }
+        if stored == KEYRING_MARKER {
+            return keyring_entry(profile, field)?.get_password().map_err(|e| {
+                SptError::Storage(format!(
+                    "could not read {field} of {profile} from the keyring: {e}"
+                ))
+            });
+        }
+        Ok(stored)
+    }
+
+    /// Removes `field` of `profile` from the keyring if `stored`, the value
+    /// [`seal`](Self::seal) produced for it, says it lives there. Other values never reach the
+    /// keyring, so this stays off Secret Service unless it was used.
+    pub fn forget(&self, profile: &str, field: &str, stored: &str) {
+        if stored != KEYRING_MARKER {
+            return;
+        }
+        if let Ok(entry) = keyring_entry(profile, field) {
+            // Nothing to clean up when the entry or the keyring itself does not exist.
+            let _ = entry.delete_password();
+        }
+    }
+
+    async fn encrypt(&self, db: &Surreal<Db>, value: &str) -> Result<String> {
+        let cipher = self.cipher(db).await?;
+        encrypt(&cipher, value)
+    }
+
+    /// Derives the key on first use, creating the salt and check value if there are none yet.
+    async fn cipher(&self, db: &Surreal<Db>) -> Result<Aes256Gcm> {
+        let mut cached = self.cipher.lock().await;
+        if let Some(cipher) = cached.as_ref() {
+            return Ok(cipher.clone());
+        }
+        let settings: Option<EncryptionSettings> = db.select(("settings", "encryption")).await?;
+        let salt = match &settings {
+            Some(settings) => general_purpose::STANDARD
+                .decode(&settings.salt)
+                .map_err(|e| SptError::Storage(format!("corrupt encryption salt: {e}")))?,
+            None => rand::thread_rng().gen::<[u8; 16]>().to_vec(),
+        };
+
+        let secret = match &self.key_file {
+            Some(path) => read_key_file(path)?,
+            None => passphrase()?.into_bytes(),
+        };
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(&secret, &salt, &mut key)
+            .map_err(|e| SptError::Storage(format!("could not derive the encryption key: {e}")))?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| SptError::Storage(format!("invalid encryption key: {e}")))?;
+
+        match settings {
+            Some(settings) => {
+                let check = settings
+                    .check
+                    .strip_prefix(ENCRYPTED_PREFIX)
+                    .unwrap_or_default();
+                if decrypt(&cipher, check).ok().as_deref() != Some(CHECK_PLAINTEXT) {
+                    return Err(SptError::Storage(
+                        "wrong passphrase or key file for the encrypted credentials".to_string(),
+                    ));
+                }
+            }
+            None => {
+                let _settings: Option<EncryptionSettings> = db
+                    .create(("settings", "encryption"))
+                    .content(EncryptionSettings {
+                        salt: general_purpose::STANDARD.encode(&salt),
+                        check: encrypt(&cipher, CHECK_PLAINTEXT)?,
+                    })
+                    .await?;
+            }
+        }
+        *cached = Some(cipher.clone());
+        Ok(cipher)
+    }
+}
+
+fn keyring_entry(profile: &str, field: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{profile}/{field}"))
+        .map_err(|e| SptError::Storage(format!("keyring: {e}")))
+}
+
+fn encrypt(cipher: &Aes256Gcm, value: &str) -> Result<String> {
+    let nonce = rand::thread_rng().gen::<[u8; NONCE_LEN]>();
+    let mut sealed = cipher
+        .encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+        .map_err(|_| SptError::Storage("encryption failed".to_string()))?;
+    sealed.splice(0..0, nonce);
+    Ok(format!(
+        "{ENCRYPTED_PREFIX}{}",
+        general_purpose::STANDARD.encode(sealed)
+    ))
+}
+
+fn decrypt(cipher: &Aes256Gcm, sealed: &str) -> Result<String> {
+    let corrupt = || SptError::Storage("could not decrypt a stored secret".to_string());
+    let sealed = general_purpose::STANDARD
+        .decode(sealed)
+        .map_err(|_| corrupt())?;
+    if sealed.len() < NONCE_LEN {
+        return Err(corrupt());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| corrupt())?;
+    String::from_utf8(plain).map_err(|_| corrupt())
+}
+
+/// Reads the key file, refusing one that other users can read.
+fn read_key_file(path: &Path) -> Result<Vec<u8>> {
+    let metadata = fs::metadata(path)
+        .map_err(|e| SptError::Config(format!("key_file {}: {e}", path.display())))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(SptError::Config(format!(
+                "key_file {} is accessible by other users (mode {:o}), run chmod 600 on it",
+                path.display(),
+                mode & 0o777
+            )));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    let key = fs::read(path)?;
+    if key.is_empty() {
+        return Err(SptError::Config(format!(
+            "key_file {} is empty",
+            path.display()
+        )));
+    }
+    Ok(key)
+}
+
+/// `$SPT_PASSPHRASE`, else asked for on the terminal.
+fn passphrase() -> Result<String> {
+    let passphrase = match env::var("SPT_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password_stderr("Passphrase for spt's credential store: ")?,
+    };
+    if passphrase.is_empty() {
+        return Err(SptError::InvalidInput(
+            "the passphrase is empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
@@ -0,0 +1,246 @@
+//! Runs `SpotifyClient` against the mock API on a free local port.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, App, HttpServer};
+use futures::TryStreamExt;
+use playlist::auth::SpotifyAccessToken;
+use playlist::error::SptError;
+use playlist::mock::{self, MockPlaylist, MockState};
+use playlist::retry::RetryPolicy;
+use playlist::spotify::{SpotifyClient, TokenFuture, TokenProvider, Volume};
+
+/// Hands out `token` and counts refreshes, which replace it with "fresh".
+struct Tokens {
+    token: Mutex<String>,
+    refreshes: Arc<AtomicUsize>,
+}
+
+impl Tokens {
+    fn new(token: &str) -> (Self, Arc<AtomicUsize>) {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let tokens = Self {
+            token: Mutex::new(token.to_string()),
+            refreshes: refreshes.clone(),
+        };
+        (tokens, refreshes)
+    }
+}
+
+impl TokenProvider for Tokens {
+    fn access_token(&self) -> TokenFuture<'_> {
+        Box::pin(async move { Ok(self.token.lock().unwrap().clone()) })
+    }
+
+    fn refresh(&self, _stale: String) -> TokenFuture<'_> {
+        Box::pin(async move {
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            let mut token = self.token.lock().unwrap();
+            *token = "fresh".to_string();
+            Ok(token.clone())
+        })
+    }
+}
+
+/// Serves `state` and returns it along with the API base URL.
+fn start(state: MockState) -> (web::Data<Mutex<MockState>>, String) {
+    let state = web::Data::new(Mutex::new(state));
+    let shared = state.clone();
+    let server = HttpServer::new(move || App::new().configure(mock::configure(shared.clone())))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    tokio::spawn(server.run());
+    (state, format!("http://{addr}/v1"))
+}
+
+fn token() -> SpotifyAccessToken {
+    SpotifyAccessToken {
+        access_token: "token".to_string(),
+        token_type: "Bearer".to_string(),
+        scope: String::new(),
+        expires_in: 3600,
+        refresh_token: "refresh".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn lists_playlists_and_their_tracks() {
+    let (_state, url) = start(MockState::default());
+    let client = SpotifyClient::new(token()).with_base_url(&url);
+
+    let playlists = client.get_all_playlists().await.unwrap();
+    let names: Vec<&str> = playlists.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["songs", "trip hop"]);
+
+    let songs = client.get_playlist(&playlists[1].id).await.unwrap();
+    let names: Vec<&str> = songs.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["Teardrop", "Angel"]);
+}
+
+#[tokio::test]
+async fn follows_next_links_across_pages() {
+    let mut state = MockState::default();
+    let uris: Vec<String> = state.tracks.iter().map(|t| t.uri()).collect();
+    state.playlists = (0..120)
+        .map(|i| MockPlaylist {
+            id: format!("list{i}"),
+            name: format!("Playlist {i}"),
+            owner: "mock".to_string(),
+            tracks: uris.iter().cycle().take(i).cloned().collect(),
+        })
+        .collect();
+    let (_state, url) = start(state);
+    let client = SpotifyClient::new(token()).with_base_url(&url);
+
+    // 50 a page.
+    let playlists = client.get_all_playlists().await.unwrap();
+    assert_eq!(playlists.len(), 120);
+    for (i, playlist) in playlists.iter().enumerate() {
+        assert_eq!(playlist.name, format!("Playlist {i}"));
+        assert_eq!(playlist.tracks, i as i64);
+    }
+
+    // 100 a page.
+    let songs: Vec<_> = client
+        .playlist_tracks("list119")
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(songs.len(), 119);
+    assert_eq!(songs[118].uri, uris[118 % uris.len()]);
+}
+
+#[tokio::test]
+async fn searches_and_controls_playback() {
+    let (state, url) = start(MockState::default());
+    let client = SpotifyClient::new(token()).with_base_url(&url);
+
+    let found = client.search_for_item("karma").await.unwrap();
+    assert_eq!(found[0].name, "Karma Police");
+
+    client
+        .start_playing(vec![found[0].uri.clone()], None)
+        .await
+        .unwrap();
+    assert_eq!(
+        client.get_currently_playing().await.unwrap().song.name,
+        "Karma Police"
+    );
+
+    let teardrop = state.lock().unwrap().tracks[6].uri();
+    client.add_to_queue(&teardrop).await.unwrap();
+    client.skip_to_next().await.unwrap();
+    assert_eq!(
+        client.get_currently_playing().await.unwrap().song.name,
+        "Teardrop"
+    );
+    client.skip_to_prev().await.unwrap();
+    assert_eq!(
+        client.get_currently_playing().await.unwrap().song.name,
+        "Karma Police"
+    );
+
+    client.pause().await.unwrap();
+    assert!(!state.lock().unwrap().player.is_playing);
+    client.shuffle(true).await.unwrap();
+    client.volume(Volume::new(30).unwrap()).await.unwrap();
+    let player = &state.lock().unwrap().player;
+    assert!(player.shuffle);
+    assert_eq!(player.volume, 30);
+}
+
+#[tokio::test]
+async fn adds_to_playlists() {
+    let (state, url) = start(MockState::default());
+    let client = SpotifyClient::new(token()).with_base_url(&url);
+
+    let uri = state.lock().unwrap().tracks[2].uri();
+    client
+        .add_to_playlist("mockpl01", vec![uri.clone()])
+        .await
+        .unwrap();
+    assert_eq!(state.lock().unwrap().playlists[0].tracks.last(), Some(&uri));
+}
+
+#[tokio::test]
+async fn refreshes_an_expired_token_once() {
+    let state = MockState {
+        expired_tokens: vec!["stale".to_string()],
+        ..MockState::default()
+    };
+    let (_state, url) = start(state);
+    let (tokens, refreshes) = Tokens::new("stale");
+    let client = SpotifyClient::new(tokens).with_base_url(&url);
+
+    assert_eq!(
+        client.get_currently_playing().await.unwrap().song.name,
+        "Airbag"
+    );
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    client.get_currently_playing().await.unwrap();
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn gives_up_when_the_refreshed_token_is_rejected_too() {
+    let state = MockState {
+        expired_tokens: vec!["stale".to_string(), "fresh".to_string()],
+        ..MockState::default()
+    };
+    let (_state, url) = start(state);
+    let (tokens, refreshes) = Tokens::new("stale");
+    let client = SpotifyClient::new(tokens).with_base_url(&url);
+
+    assert!(matches!(
+        client.get_currently_playing().await,
+        Err(SptError::AuthExpired)
+    ));
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn waits_out_retry_after() {
+    let state = MockState {
+        throttle: 2,
+        ..MockState::default()
+    };
+    let (state, url) = start(state);
+    let client = SpotifyClient::new(token()).with_base_url(&url);
+
+    let started = Instant::now();
+    assert_eq!(
+        client.get_currently_playing().await.unwrap().song.name,
+        "Airbag"
+    );
+    assert!(started.elapsed() >= Duration::from_secs(2));
+    assert_eq!(state.lock().unwrap().throttle, 0);
+
+    // Throttled requests never reached the endpoint, so a POST is safe to retry.
+    state.lock().unwrap().throttle = 1;
+    let uri = state.lock().unwrap().tracks[0].uri();
+    client.add_to_queue(&uri).await.unwrap();
+    assert_eq!(state.lock().unwrap().player.queue, [uri]);
+}
+
+#[tokio::test]
+async fn reports_429_when_out_of_retries() {
+    let state = MockState {
+        throttle: 1,
+        ..MockState::default()
+    };
+    let (_state, url) = start(state);
+    let client = SpotifyClient::new(token())
+        .with_base_url(&url)
+        .with_retry_policy(RetryPolicy::none());
+
+    assert!(matches!(
+        client.get_currently_playing().await,
+        Err(SptError::RateLimited {
+            retry_after: Some(wait)
+        }) if wait == Duration::from_secs(1)
+    ));
+}